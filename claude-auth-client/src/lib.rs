@@ -55,6 +55,13 @@ pub struct ClientConfig {
     pub cache_path: Option<PathBuf>,
     /// Optional namespace to split per-account caches.
     pub account_namespace: Option<String>,
+    /// Router-side OAuth provider name (router default when `None`).
+    pub provider: Option<String>,
+    /// Secret for service clients the router serves with the
+    /// client_credentials grant; sent as HTTP Basic with `client_name`.
+    pub client_secret: Option<String>,
+    /// Ask the router to open the flow in a free pane of its choosing.
+    pub auto_assign: bool,
     /// Poll interval when waiting for approval.
     pub poll_interval: StdDuration,
     /// Max time to wait for approval before failing.
@@ -77,18 +84,29 @@ impl ClientConfig {
             .ok()
             .or_else(|| std::env::var("CLAUDE_PROFILE").ok());
 
+        let provider = std::env::var("CLAUDE_AUTH_PROVIDER").ok();
+        let client_secret = std::env::var("CLAUDE_CLIENT_SECRET").ok();
+        let auto_assign = std::env::var("CLAUDE_AUTH_AUTO_ASSIGN")
+            .is_ok_and(|v| matches!(v.as_str(), "1" | "true" | "yes" | "on"));
+
         Ok(Self {
             router_base_url: router,
             client_name,
             hostname,
             cache_path: None,
             account_namespace,
+            provider,
+            client_secret,
+            auto_assign,
             poll_interval: StdDuration::from_secs(2),
             max_wait: StdDuration::from_secs(300),
         })
     }
 }
 
+const TOKEN_EXCHANGE_GRANT: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 enum RequestStatus {
@@ -104,11 +122,14 @@ enum RequestStatus {
 struct CreateTokenResponse {
     request_id: String,
     status: RequestStatus,
+    #[serde(default)]
+    token: Option<TokenBundle>,
+    #[serde(default)]
+    error: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct StatusResponse {
-    request_id: String,
     status: RequestStatus,
     token: Option<TokenBundle>,
     error: Option<String>,
@@ -119,6 +140,22 @@ struct CreateTokenBody<'a> {
     client_name: &'a str,
     hostname: &'a str,
     scopes: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grant_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    auto_assign: bool,
+}
+
+/// On-disk cache entry; `grant_id` lets a later call extend the grant with
+/// extra scopes instead of re-consenting to everything.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedToken {
+    #[serde(default)]
+    grant_id: Option<String>,
+    #[serde(flatten)]
+    token: TokenBundle,
 }
 
 /// Obtain a valid token, using cache when possible.
@@ -126,24 +163,126 @@ pub fn get_token(cfg: &ClientConfig, scopes: &[&str]) -> Result<TokenBundle, Aut
     let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
     let http = Client::new();
 
+    let mut grant_id = None;
     if let Some(path) = cache_path(cfg)? {
-        if let Some(token) = load_cached_token(&path)? {
-            if is_token_valid(&token) {
-                return Ok(token);
+        if let Some(cached) = load_cached_token(&path)? {
+            if is_token_valid(&cached.token) {
+                if covers_scopes(&cached.token, &scopes) {
+                    return Ok(cached.token);
+                }
+                // Still valid but missing scopes: ask only for the delta.
+                grant_id = cached.grant_id;
             }
         }
     }
 
-    let created = create_token_request(&http, cfg, &scopes)?;
-    let token = wait_for_approval(&http, cfg, &created.request_id)?;
+    let created = create_token_request(&http, cfg, &scopes, grant_id.as_deref())?;
+    let token = match created.status {
+        // Service clients are answered immediately via client_credentials.
+        RequestStatus::Approved => created
+            .token
+            .ok_or_else(|| AuthClientError::Router("approved but no token present".into()))?,
+        RequestStatus::Error => {
+            return Err(AuthClientError::Router(
+                created.error.unwrap_or_else(|| "unknown error".into()),
+            ))
+        }
+        RequestStatus::Denied | RequestStatus::Cancelled => return Err(AuthClientError::Denied),
+        RequestStatus::Pending | RequestStatus::InProgress => {
+            wait_for_approval(&http, cfg, &created.request_id)?
+        }
+    };
 
     if let Some(path) = cache_path(cfg)? {
-        save_token(&path, &token)?;
+        save_token(
+            &path,
+            &CachedToken {
+                grant_id: Some(created.request_id),
+                token: token.clone(),
+            },
+        )?;
     }
 
     Ok(token)
 }
 
+/// Trade a router-issued token for a narrower one (RFC 8693), e.g. to hand
+/// to a subprocess. `scopes` must be a subset of the subject token's scopes;
+/// an empty slice keeps them all. The result is not cached.
+pub fn exchange_token(
+    cfg: &ClientConfig,
+    subject: &TokenBundle,
+    scopes: &[&str],
+    audience: Option<&str>,
+) -> Result<TokenBundle, AuthClientError> {
+    #[derive(Deserialize)]
+    struct ExchangeResponse {
+        access_token: String,
+        token_type: String,
+        #[serde(default)]
+        expires_in: Option<i64>,
+        #[serde(default)]
+        scope: Option<String>,
+        #[serde(default)]
+        refresh_token: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct ExchangeError {
+        error: String,
+        #[serde(default)]
+        error_description: Option<String>,
+    }
+
+    let url = format!(
+        "{}/v1/token-exchange",
+        cfg.router_base_url.trim_end_matches('/')
+    );
+
+    let mut form = vec![
+        ("grant_type", TOKEN_EXCHANGE_GRANT.to_string()),
+        ("subject_token", subject.access_token.clone()),
+        ("subject_token_type", ACCESS_TOKEN_TYPE.to_string()),
+    ];
+    if !scopes.is_empty() {
+        form.push(("scope", scopes.join(" ")));
+    }
+    if let Some(audience) = audience {
+        form.push(("audience", audience.to_string()));
+    }
+
+    let resp = Client::new()
+        .post(&url)
+        .form(&form)
+        .send()
+        .map_err(|e| AuthClientError::Http(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        return Err(match resp.json::<ExchangeError>() {
+            Ok(err) => AuthClientError::Router(match err.error_description {
+                Some(desc) => format!("{}: {desc}", err.error),
+                None => err.error,
+            }),
+            Err(_) => AuthClientError::Http(format!("router returned {status} on exchange")),
+        });
+    }
+
+    let token: ExchangeResponse = resp
+        .json()
+        .map_err(|e| AuthClientError::Http(format!("parse exchange response: {e}")))?;
+
+    Ok(TokenBundle {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: token
+            .expires_in
+            .map(|secs| Utc::now() + Duration::seconds(secs)),
+        token_type: token.token_type,
+        scope: token.scope,
+    })
+}
+
 fn cache_path(cfg: &ClientConfig) -> Result<Option<PathBuf>, AuthClientError> {
     if let Some(p) = &cfg.cache_path {
         return Ok(Some(p.clone()));
@@ -184,19 +323,19 @@ fn sanitize_component(s: &str) -> String {
     }
 }
 
-fn load_cached_token(path: &Path) -> Result<Option<TokenBundle>, AuthClientError> {
+fn load_cached_token(path: &Path) -> Result<Option<CachedToken>, AuthClientError> {
     if !path.exists() {
         return Ok(None);
     }
 
     let data =
         fs::read_to_string(path).map_err(|e| AuthClientError::Cache(format!("read cache: {e}")))?;
-    let token: TokenBundle = serde_json::from_str(&data)
+    let token: CachedToken = serde_json::from_str(&data)
         .map_err(|e| AuthClientError::Cache(format!("parse cache: {e}")))?;
     Ok(Some(token))
 }
 
-fn save_token(path: &Path, token: &TokenBundle) -> Result<(), AuthClientError> {
+fn save_token(path: &Path, token: &CachedToken) -> Result<(), AuthClientError> {
     let data = serde_json::to_string_pretty(token)
         .map_err(|e| AuthClientError::Cache(format!("serialize cache: {e}")))?;
 
@@ -212,6 +351,17 @@ fn save_token(path: &Path, token: &TokenBundle) -> Result<(), AuthClientError> {
     Ok(())
 }
 
+/// Tokens without a `scope` are assumed to carry what was asked for.
+fn covers_scopes(token: &TokenBundle, scopes: &[String]) -> bool {
+    match &token.scope {
+        Some(granted) => {
+            let granted: Vec<&str> = granted.split_whitespace().collect();
+            scopes.iter().all(|s| granted.contains(&s.as_str()))
+        }
+        None => true,
+    }
+}
+
 fn is_token_valid(token: &TokenBundle) -> bool {
     match token.expires_at {
         Some(exp) => exp - Duration::seconds(30) > Utc::now(),
@@ -223,6 +373,7 @@ fn create_token_request(
     http: &Client,
     cfg: &ClientConfig,
     scopes: &[String],
+    grant_id: Option<&str>,
 ) -> Result<CreateTokenResponse, AuthClientError> {
     let url = format!(
         "{}/v1/token-requests",
@@ -233,17 +384,30 @@ fn create_token_request(
         client_name: &cfg.client_name,
         hostname: &cfg.hostname,
         scopes,
+        provider: cfg.provider.as_deref(),
+        grant_id,
+        auto_assign: cfg.auto_assign,
     };
 
-    let resp = http
-        .post(&url)
-        .json(&body)
+    let mut req = http.post(&url).json(&body);
+    if let Some(secret) = &cfg.client_secret {
+        req = req.basic_auth(&cfg.client_name, Some(secret));
+    }
+
+    let resp = req
         .send()
         .map_err(|e| AuthClientError::Http(e.to_string()))?;
     if !resp.status().is_success() {
+        let status = resp.status();
+        if let Some(error) = resp
+            .json::<CreateTokenResponse>()
+            .ok()
+            .and_then(|body| body.error)
+        {
+            return Err(AuthClientError::Router(error));
+        }
         return Err(AuthClientError::Http(format!(
-            "router returned {} on create",
-            resp.status()
+            "router returned {status} on create"
         )));
     }
 
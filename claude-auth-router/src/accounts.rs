@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::drivers::DriverConfig;
+
+const CLAUDE_CODE_URL: &str = "https://claude.ai/code";
+const CHATGPT_URL: &str = "https://chatgpt.com";
+
+#[derive(Debug, Error)]
+pub enum AccountsError {
+    #[error("read accounts file: {0}")]
+    Read(String),
+    #[error("parse accounts file: {0}")]
+    Parse(String),
+    #[error("duplicate account id {0}")]
+    Duplicate(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountKind {
+    ClaudeCode,
+    ClaudeWorkspace,
+    Chatgpt,
+}
+
+/// A control-browser pane the router may send flows to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub id: u32,
+    pub name: String,
+    pub kind: AccountKind,
+    /// Requests whose verified ID token email differs are failed.
+    #[serde(default)]
+    pub expected_email: Option<String>,
+    /// Only flows for this provider may use the pane; any provider when unset.
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Page the pane starts on, used to send it back after the callback page.
+    #[serde(default)]
+    pub startup_url: Option<String>,
+    /// How authorization URLs are opened for this account; the router-wide
+    /// default when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub driver: Option<DriverConfig>,
+}
+
+impl Account {
+    pub fn accepts_provider(&self, provider: &str) -> bool {
+        self.provider.as_deref().is_none_or(|p| p == provider)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccountRegistry {
+    accounts: Vec<Account>,
+}
+
+impl AccountRegistry {
+    /// Ids, kinds and startup pages of the control browser's eight panes.
+    /// Login emails are left unset: the browser's `prefillEmail`s only fill
+    /// in forms, while an `expected_email` here fails mismatching logins.
+    pub fn defaults() -> Self {
+        let pane = |id: u32, kind: AccountKind| {
+            let (label, startup_url) = match kind {
+                AccountKind::ClaudeCode => ("Claude Code", CLAUDE_CODE_URL),
+                AccountKind::ClaudeWorkspace => ("Claude Workspace", CLAUDE_CODE_URL),
+                AccountKind::Chatgpt => ("ChatGPT", CHATGPT_URL),
+            };
+            Account {
+                id,
+                name: format!("{label} #{id}"),
+                kind,
+                expected_email: None,
+                provider: None,
+                startup_url: Some(startup_url.to_string()),
+                driver: None,
+            }
+        };
+
+        Self {
+            accounts: (1..=4)
+                .map(|id| pane(id, AccountKind::ClaudeCode))
+                .chain((5..=7).map(|id| pane(id, AccountKind::ClaudeWorkspace)))
+                .chain(std::iter::once(pane(8, AccountKind::Chatgpt)))
+                .collect(),
+        }
+    }
+
+    /// Reads a JSON array of accounts, replacing the defaults entirely.
+    pub fn from_file(path: &str) -> Result<Self, AccountsError> {
+        let data = std::fs::read_to_string(path).map_err(|e| AccountsError::Read(e.to_string()))?;
+        let accounts: Vec<Account> =
+            serde_json::from_str(&data).map_err(|e| AccountsError::Parse(e.to_string()))?;
+
+        let mut seen = std::collections::HashSet::new();
+        if let Some(dup) = accounts.iter().find(|a| !seen.insert(a.id)) {
+            return Err(AccountsError::Duplicate(dup.id));
+        }
+        Ok(Self { accounts })
+    }
+
+    /// Layers the older per-account env maps over the registry.
+    pub fn with_overrides(
+        mut self,
+        expected_emails: &HashMap<u32, String>,
+        startup_urls: &HashMap<u32, String>,
+    ) -> Self {
+        for account in &mut self.accounts {
+            if let Some(email) = expected_emails.get(&account.id) {
+                account.expected_email = Some(email.clone());
+            }
+            if let Some(url) = startup_urls.get(&account.id) {
+                account.startup_url = Some(url.clone());
+            }
+        }
+        self
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Account> {
+        self.accounts.iter().find(|a| a.id == id)
+    }
+
+    pub fn all(&self) -> &[Account] {
+        &self.accounts
+    }
+}
@@ -0,0 +1,109 @@
+use base64::Engine;
+use chrono::Utc;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::Serialize;
+use url::form_urlencoded;
+use uuid::Uuid;
+
+use crate::config::{OAuthConfig, TokenAuthMethod};
+use crate::oauth::OAuthError;
+
+/// RFC 7523 assertion type for `private_key_jwt`.
+const CLIENT_ASSERTION_TYPE: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// Client assertions are single-use; keep their lifetime short.
+const ASSERTION_LIFETIME_SECS: i64 = 60;
+
+#[derive(Serialize)]
+struct AssertionClaims<'a> {
+    iss: &'a str,
+    sub: &'a str,
+    aud: &'a str,
+    jti: String,
+    iat: i64,
+    exp: i64,
+}
+
+/// Adds client credentials to a token-endpoint style request according to
+/// the provider's configured auth method. `audience` is the endpoint URL.
+pub fn authenticate(
+    cfg: &OAuthConfig,
+    builder: reqwest::RequestBuilder,
+    form: &mut Vec<(&'static str, String)>,
+    audience: &str,
+) -> Result<reqwest::RequestBuilder, OAuthError> {
+    match cfg.token_auth_method {
+        TokenAuthMethod::ClientSecretPost => {
+            form.push(("client_id", cfg.client_id.clone()));
+            form.push(("client_secret", cfg.client_secret.clone()));
+            Ok(builder)
+        }
+        TokenAuthMethod::ClientSecretBasic => Ok(builder.header(
+            reqwest::header::AUTHORIZATION,
+            basic_authorization(&cfg.client_id, &cfg.client_secret),
+        )),
+        TokenAuthMethod::PrivateKeyJwt => {
+            form.push(("client_id", cfg.client_id.clone()));
+            form.push(("client_assertion_type", CLIENT_ASSERTION_TYPE.into()));
+            form.push(("client_assertion", client_assertion(cfg, audience)?));
+            Ok(builder)
+        }
+        TokenAuthMethod::None => {
+            form.push(("client_id", cfg.client_id.clone()));
+            Ok(builder)
+        }
+    }
+}
+
+/// RFC 6749 §2.3.1: id and secret are form-encoded before base64.
+fn basic_authorization(client_id: &str, client_secret: &str) -> String {
+    let id: String = form_urlencoded::byte_serialize(client_id.as_bytes()).collect();
+    let secret: String = form_urlencoded::byte_serialize(client_secret.as_bytes()).collect();
+    let encoded = base64::engine::general_purpose::STANDARD.encode(format!("{id}:{secret}"));
+    format!("Basic {encoded}")
+}
+
+fn client_assertion(cfg: &OAuthConfig, audience: &str) -> Result<String, OAuthError> {
+    let path = cfg
+        .private_key_path
+        .as_deref()
+        .ok_or_else(|| OAuthError::Config("private key for private_key_jwt".into()))?;
+    let pem = std::fs::read(path)
+        .map_err(|e| OAuthError::Config(format!("read private key {path}: {e}")))?;
+
+    let alg: Algorithm = cfg
+        .private_key_alg
+        .parse()
+        .map_err(|e| OAuthError::Config(format!("private key alg: {e}")))?;
+    let key = match alg {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => EncodingKey::from_rsa_pem(&pem),
+        Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(&pem),
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(&pem),
+        other => {
+            return Err(OAuthError::Config(format!(
+                "{other:?} is not an asymmetric algorithm"
+            )))
+        }
+    }
+    .map_err(|e| OAuthError::Config(format!("private key: {e}")))?;
+
+    let mut header = Header::new(alg);
+    header.kid = cfg.private_key_id.clone();
+
+    let now = Utc::now().timestamp();
+    let claims = AssertionClaims {
+        iss: &cfg.client_id,
+        sub: &cfg.client_id,
+        aud: audience,
+        jti: Uuid::new_v4().to_string(),
+        iat: now,
+        exp: now + ASSERTION_LIFETIME_SECS,
+    };
+
+    encode(&header, &claims, &key).map_err(|e| OAuthError::Config(format!("sign assertion: {e}")))
+}
@@ -1,43 +1,418 @@
 use serde::Deserialize;
 
+use crate::drivers::DriverConfig;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::str::FromStr;
+
+/// Name of the provider built from the plain `OAUTH_*` variables.
+pub const DEFAULT_PROVIDER: &str = "default";
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct OAuthConfig {
     pub client_id: String,
     pub client_secret: String,
-    pub auth_url: String,
-    pub token_url: String,
+    /// OIDC issuer; when set, endpoints are discovered from
+    /// `<issuer>/.well-known/openid-configuration`.
+    pub issuer: Option<String>,
+    /// Explicit endpoints always override discovered ones.
+    pub auth_url: Option<String>,
+    pub token_url: Option<String>,
+    pub revocation_url: Option<String>,
+    pub introspection_url: Option<String>,
+    pub userinfo_url: Option<String>,
+    pub jwks_uri: Option<String>,
     pub redirect_uri: String,
+    /// Provider-hosted redirect that displays the code for manual entry, used
+    /// when the router's callback is unreachable from the pane.
+    pub oob_redirect_uri: Option<String>,
+    /// How long discovered metadata is cached before being refreshed.
+    pub discovery_ttl_secs: u64,
+    /// How the router authenticates itself to the token endpoint.
+    pub token_auth_method: TokenAuthMethod,
+    /// PEM private key used to sign `private_key_jwt` client assertions.
+    pub private_key_path: Option<String>,
+    /// JWS algorithm for client assertions, e.g. `RS256` or `ES256`.
+    pub private_key_alg: String,
+    /// `kid` header for client assertions, when the provider needs it.
+    pub private_key_id: Option<String>,
+    pub connect_timeout_secs: u64,
+    pub read_timeout_secs: u64,
+    /// Extra attempts for token requests that failed transiently.
+    pub max_retries: u32,
+    /// Base delay for exponential backoff between retries.
+    pub retry_backoff_ms: u64,
+    /// What to do when the provider grants fewer scopes than requested.
+    pub scope_policy: ScopePolicy,
+    /// Provider's token endpoint accepts RFC 8693 token exchange.
+    pub token_exchange: bool,
+    /// Authorization parameter (set to `true`) asking the provider to include
+    /// previously granted scopes, e.g. `include_granted_scopes`.
+    pub incremental_auth_param: Option<String>,
+    /// Route every flow for this provider to the verification pane.
+    pub verification: bool,
+    /// Extra origins a login may pass through (e.g. an SSO provider) before
+    /// returning; when set, leaving these and the provider's own origins
+    /// abandons it. Empty disables that check.
+    pub login_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScopePolicy {
+    /// Approve, flagging the request as partial and listing missing scopes.
+    #[default]
+    AllowPartial,
+    /// Fail the request unless every requested scope was granted.
+    RequireAll,
+}
+
+impl FromStr for ScopePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow_partial" => Ok(Self::AllowPartial),
+            "require_all" => Ok(Self::RequireAll),
+            other => Err(format!("unsupported scope policy: {other}")),
+        }
+    }
+}
+
+/// How `account_id: "auto"` picks a pane.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionStrategy {
+    /// The pane whose last flow is oldest.
+    #[default]
+    LeastRecentlyUsed,
+    /// Cycle through panes in registry order.
+    RoundRobin,
+    /// Reuse the pane a client/hostname last used, when it is free.
+    Sticky,
+}
+
+impl FromStr for SelectionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lru" | "least_recently_used" => Ok(Self::LeastRecentlyUsed),
+            "round_robin" => Ok(Self::RoundRobin),
+            "sticky" => Ok(Self::Sticky),
+            other => Err(format!("unsupported selection strategy: {other}")),
+        }
+    }
+}
+
+/// Token endpoint client authentication methods (RFC 7591 names).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenAuthMethod {
+    #[default]
+    ClientSecretPost,
+    ClientSecretBasic,
+    PrivateKeyJwt,
+    /// Public client: only `client_id` is sent.
+    None,
+}
+
+impl FromStr for TokenAuthMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client_secret_post" => Ok(Self::ClientSecretPost),
+            "client_secret_basic" => Ok(Self::ClientSecretBasic),
+            "private_key_jwt" => Ok(Self::PrivateKeyJwt),
+            "none" => Ok(Self::None),
+            other => Err(format!("unsupported token auth method: {other}")),
+        }
+    }
+}
+
+impl OAuthConfig {
+    fn from_env(provider: Option<&str>) -> Self {
+        let var = |suffix: &str| {
+            provider
+                .and_then(|name| env_opt(&format!("OAUTH_{}_{suffix}", env_key(name))))
+                .or_else(|| env_opt(&format!("OAUTH_{suffix}")))
+        };
+
+        Self {
+            client_id: var("CLIENT_ID").unwrap_or_default(),
+            client_secret: var("CLIENT_SECRET").unwrap_or_default(),
+            issuer: var("ISSUER"),
+            auth_url: var("AUTH_URL"),
+            token_url: var("TOKEN_URL"),
+            revocation_url: var("REVOCATION_URL"),
+            introspection_url: var("INTROSPECTION_URL"),
+            userinfo_url: var("USERINFO_URL"),
+            jwks_uri: var("JWKS_URI"),
+            redirect_uri: var("REDIRECT_URI")
+                .unwrap_or_else(|| "http://127.0.0.1:7777/oauth/callback".into()),
+            oob_redirect_uri: var("OOB_REDIRECT_URI"),
+            discovery_ttl_secs: var("DISCOVERY_TTL_SECS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+            token_auth_method: var("TOKEN_AUTH_METHOD")
+                .and_then(|v| match v.parse() {
+                    Ok(method) => Some(method),
+                    Err(e) => {
+                        tracing::warn!(provider = ?provider, error = %e, "using client_secret_post");
+                        None
+                    }
+                })
+                .unwrap_or_default(),
+            private_key_path: var("PRIVATE_KEY_PATH"),
+            private_key_alg: var("PRIVATE_KEY_ALG").unwrap_or_else(|| "RS256".into()),
+            private_key_id: var("PRIVATE_KEY_ID"),
+            connect_timeout_secs: var("CONNECT_TIMEOUT_SECS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            read_timeout_secs: var("READ_TIMEOUT_SECS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            max_retries: var("MAX_RETRIES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+            retry_backoff_ms: var("RETRY_BACKOFF_MS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(250),
+            scope_policy: var("SCOPE_POLICY")
+                .and_then(|v| match v.parse() {
+                    Ok(policy) => Some(policy),
+                    Err(e) => {
+                        tracing::warn!(provider = ?provider, error = %e, "using allow_partial");
+                        None
+                    }
+                })
+                .unwrap_or_default(),
+            token_exchange: var("TOKEN_EXCHANGE").is_some_and(|v| truthy(&v)),
+            incremental_auth_param: var("INCREMENTAL_AUTH_PARAM"),
+            verification: var("VERIFICATION").is_some_and(|v| truthy(&v)),
+            login_origins: var("LOGIN_ORIGINS")
+                .map(|v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|o| !o.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ControlBrowserConfig {
     pub base_url: String,
+    /// Seconds between reachability probes of the control server.
+    pub health_interval_secs: u64,
+    pub probe_timeout_ms: u64,
+    /// Shared secret for HMAC-signing every control request.
+    pub signing_secret: Option<String>,
+    /// Extra attempts for commands that hit a connection failure or 5xx.
+    pub max_retries: u32,
+    pub retry_backoff_ms: u64,
+    /// Consecutive failed commands before the circuit opens.
+    pub breaker_threshold: u32,
+    /// How long an open circuit refuses commands.
+    pub breaker_cooldown_secs: u64,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct RouterConfig {
     pub bind_addr: String,
-    pub oauth: OAuthConfig,
+    /// OAuth providers keyed by name; requests pick one via `provider`.
+    pub providers: HashMap<String, OAuthConfig>,
+    /// Provider used when a request does not name one.
+    pub default_provider: String,
     pub control_browser: ControlBrowserConfig,
+    /// JSON account registry replacing the built-in eight panes.
+    pub accounts_file: Option<String>,
+    /// Driver for accounts that do not choose one.
+    pub default_driver: DriverConfig,
+    /// Pane verification flows open in (the control browser's
+    /// `VERIFICATION_VIEW_ID`).
+    pub verification_account_id: u32,
+    /// Login email each pane is expected to authenticate as, keyed by account id;
+    /// overrides the registry.
+    pub expected_emails: HashMap<u32, String>,
+    pub introspection: IntrospectionConfig,
+    /// How long a pane stays reserved for an in-progress request.
+    pub account_lease_ttl_secs: u64,
+    pub selection_strategy: SelectionStrategy,
+    /// JSON file the per-account timer schedule is persisted to.
+    pub timers_file: Option<String>,
+    /// Receives a POST when an account's cooldown ends.
+    pub availability_webhook: Option<String>,
+    /// Page each pane starts on, keyed by account id; overrides the registry.
+    pub startup_urls: HashMap<u32, String>,
+    pub callback_page: CallbackPageConfig,
+    /// Non-interactive clients served with the client_credentials grant, as
+    /// client name -> secret they authenticate to the router with.
+    pub service_clients: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CallbackPageConfig {
+    /// Try to close the window after the callback page is shown.
+    pub auto_close: bool,
+    /// Navigate the pane back to its startup URL instead (takes precedence).
+    pub return_to_startup: bool,
+    /// Seconds the page stays visible before closing or navigating.
+    pub delay_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IntrospectionConfig {
+    /// Services allowed to call the introspection endpoint, as name -> secret.
+    pub callers: HashMap<String, String>,
+    /// Also ask the issuing provider's introspection endpoint before reporting
+    /// a router-issued token as active.
+    pub proxy_upstream: bool,
 }
 
 impl RouterConfig {
     pub fn from_env() -> Self {
+        // `OAUTH_PROVIDERS=claude-code,chatgpt` declares named providers whose
+        // settings come from `OAUTH_<NAME>_*`, falling back to the shared `OAUTH_*`.
+        let names: Vec<String> = env_opt("OAUTH_PROVIDERS")
+            .map(|v| {
+                v.split(',')
+                    .map(|n| n.trim().to_string())
+                    .filter(|n| !n.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let providers: HashMap<String, OAuthConfig> = if names.is_empty() {
+            HashMap::from([(DEFAULT_PROVIDER.to_string(), OAuthConfig::from_env(None))])
+        } else {
+            names
+                .iter()
+                .map(|n| (n.clone(), OAuthConfig::from_env(Some(n))))
+                .collect()
+        };
+
+        let default_provider = env_opt("OAUTH_DEFAULT_PROVIDER")
+            .or_else(|| names.first().cloned())
+            .unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+
         Self {
             bind_addr: std::env::var("ROUTER_BIND_ADDR")
                 .unwrap_or_else(|_| "127.0.0.1:7777".into()),
-            oauth: OAuthConfig {
-                client_id: std::env::var("OAUTH_CLIENT_ID").unwrap_or_default(),
-                client_secret: std::env::var("OAUTH_CLIENT_SECRET").unwrap_or_default(),
-                auth_url: std::env::var("OAUTH_AUTH_URL").unwrap_or_default(),
-                token_url: std::env::var("OAUTH_TOKEN_URL").unwrap_or_default(),
-                redirect_uri: std::env::var("OAUTH_REDIRECT_URI")
-                    .unwrap_or_else(|_| "http://127.0.0.1:7777/oauth/callback".into()),
-            },
+            providers,
+            default_provider,
             control_browser: ControlBrowserConfig {
                 base_url: std::env::var("CONTROL_BROWSER_URL")
                     .unwrap_or_else(|_| "http://127.0.0.1:7780".into()),
+                health_interval_secs: env_opt("CONTROL_BROWSER_HEALTH_INTERVAL_SECS")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(10),
+                probe_timeout_ms: env_opt("CONTROL_BROWSER_PROBE_TIMEOUT_MS")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(2000),
+                signing_secret: env_opt("CONTROL_BROWSER_SECRET"),
+                max_retries: env_opt("CONTROL_BROWSER_MAX_RETRIES")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(2),
+                retry_backoff_ms: env_opt("CONTROL_BROWSER_RETRY_BACKOFF_MS")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(200),
+                breaker_threshold: env_opt("CONTROL_BROWSER_BREAKER_THRESHOLD")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(5),
+                breaker_cooldown_secs: env_opt("CONTROL_BROWSER_BREAKER_COOLDOWN_SECS")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(30),
             },
+            accounts_file: env_opt("ROUTER_ACCOUNTS_FILE"),
+            verification_account_id: env_opt("VERIFICATION_ACCOUNT_ID")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            default_driver: env_opt("ROUTER_BROWSER_DRIVER")
+                .and_then(|v| match v.parse() {
+                    Ok(driver) => Some(driver),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "using control_browser driver");
+                        None
+                    }
+                })
+                .unwrap_or(DriverConfig::ControlBrowser),
+            expected_emails: env_opt("ACCOUNT_EXPECTED_EMAILS")
+                .map(|v| parse_map(&v))
+                .unwrap_or_default(),
+            introspection: IntrospectionConfig {
+                callers: env_opt("INTROSPECTION_CLIENTS")
+                    .map(|v| parse_map(&v))
+                    .unwrap_or_default(),
+                proxy_upstream: env_flag("INTROSPECTION_PROXY_UPSTREAM"),
+            },
+            account_lease_ttl_secs: env_opt("ACCOUNT_LEASE_TTL_SECS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(600),
+            selection_strategy: env_opt("ACCOUNT_SELECTION_STRATEGY")
+                .and_then(|v| match v.parse() {
+                    Ok(strategy) => Some(strategy),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "using lru account selection");
+                        None
+                    }
+                })
+                .unwrap_or_default(),
+            timers_file: env_opt("ROUTER_TIMERS_FILE"),
+            availability_webhook: env_opt("ACCOUNT_AVAILABLE_WEBHOOK_URL"),
+            startup_urls: env_opt("ACCOUNT_STARTUP_URLS")
+                .map(|v| parse_map(&v))
+                .unwrap_or_default(),
+            callback_page: CallbackPageConfig {
+                auto_close: env_flag("CALLBACK_PAGE_AUTO_CLOSE"),
+                return_to_startup: env_flag("CALLBACK_PAGE_RETURN_TO_STARTUP"),
+                delay_secs: env_opt("CALLBACK_PAGE_DELAY_SECS")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(5),
+            },
+            service_clients: env_opt("CLIENT_CREDENTIALS_CLIENTS")
+                .map(|v| parse_map(&v))
+                .unwrap_or_default(),
         }
     }
 }
+
+/// Reads an env var, treating unset and empty values alike.
+fn env_opt(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn env_flag(key: &str) -> bool {
+    env_opt(key).is_some_and(|v| truthy(&v))
+}
+
+fn truthy(v: &str) -> bool {
+    matches!(v, "1" | "true" | "yes" | "on")
+}
+
+/// Parses `1=alice@example.com,2=bob@example.com`, skipping malformed entries.
+fn parse_map<K: FromStr + Eq + Hash>(raw: &str) -> HashMap<K, String> {
+    raw.split(',')
+        .filter_map(|entry| {
+            let (key, value) = entry.split_once('=')?;
+            Some((key.trim().parse().ok()?, value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// `claude-code` -> `CLAUDE_CODE`, for building per-provider env var names.
+fn env_key(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
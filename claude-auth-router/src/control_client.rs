@@ -1,19 +1,227 @@
 use crate::config::ControlBrowserConfig;
+use crate::oauth::backoff_delay;
+use crate::signing::RequestSigner;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use parking_lot::{Mutex, RwLock};
 use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration as StdDuration, Instant};
 use thiserror::Error;
 
+/// Furthest ahead the control browser accepts a countdown timer.
+pub const MAX_TIMER_WINDOW_DAYS: i64 = 6;
+
+/// Response header on `GET /` identifying the control server's process.
+const BOOT_ID_HEADER: &str = "x-control-boot-id";
+
 #[derive(Debug, Error)]
 pub enum ControlClientError {
+    /// The last probe (or request) could not reach the control server.
+    #[error("control browser unavailable: {0}")]
+    Unavailable(String),
     #[error("http error: {0}")]
     Http(String),
     #[error("invalid url: {0}")]
     Url(String),
+    /// The control browser answered with an error status, e.g. `not_found`
+    /// for an unknown account or `cannot_go_back`.
+    #[error("control browser rejected request ({status}): {reason}")]
+    Rejected { status: u16, reason: String },
+    #[error("invalid timer deadline: {0}")]
+    InvalidDeadline(String),
+    /// Too many consecutive failures; commands are refused until `until`.
+    #[error("control browser circuit open until {until}")]
+    CircuitOpen { until: DateTime<Utc> },
+}
+
+/// `action` values accepted by `/navigate`.
+// Only used by [`ControlClient::navigate`], which no route calls yet.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NavigateAction {
+    Back,
+    Forward,
+    Reload,
+}
+
+#[allow(dead_code)]
+impl NavigateAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Back => "back",
+            Self::Forward => "forward",
+            Self::Reload => "reload",
+        }
+    }
+}
+
+impl fmt::Display for NavigateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NavigateAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "back" => Ok(Self::Back),
+            "forward" => Ok(Self::Forward),
+            "reload" => Ok(Self::Reload),
+            other => Err(format!("unsupported navigate action: {other}")),
+        }
+    }
+}
+
+/// A countdown target the control browser will accept: in the future and no
+/// more than [`MAX_TIMER_WINDOW_DAYS`] ahead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerDeadline(DateTime<Utc>);
+
+impl TimerDeadline {
+    pub fn new(at: DateTime<Utc>) -> Result<Self, ControlClientError> {
+        let now = Utc::now();
+        if at <= now {
+            return Err(ControlClientError::InvalidDeadline(format!(
+                "{} is not in the future",
+                at.to_rfc3339()
+            )));
+        }
+        if at > now + Duration::days(MAX_TIMER_WINDOW_DAYS) {
+            return Err(ControlClientError::InvalidDeadline(format!(
+                "{} is more than {MAX_TIMER_WINDOW_DAYS} days ahead",
+                at.to_rfc3339()
+            )));
+        }
+        Ok(Self(at))
+    }
+
+    pub fn at(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ControlResponse {
+    status: String,
+}
+
+/// Reachability of the control server as seen by the periodic probe.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ControlHealth {
+    pub reachable: bool,
+    /// Round trip of the last successful probe.
+    pub latency_ms: Option<u64>,
+    pub last_checked: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Boot id the control server reported on the last successful probe; it
+    /// changes whenever the Electron process restarts.
+    pub boot_id: Option<String>,
+    pub circuit: CircuitStatus,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Commands flow normally.
+    #[default]
+    Closed,
+    /// Commands are refused without contacting the control server.
+    Open,
+    /// The cooldown has passed; the next command is a trial.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CircuitStatus {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_until: Option<DateTime<Utc>>,
+}
+
+/// Stops hammering a dead control server: after `threshold` consecutive
+/// failed commands it opens for `cooldown`, then lets one trial through.
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    status: CircuitStatus,
+    /// When the half-open trial was admitted; other commands are refused
+    /// until it resolves, or for one cooldown if its outcome never arrives.
+    trial_started: Option<DateTime<Utc>>,
+}
+
+impl CircuitBreaker {
+    fn status(&mut self) -> CircuitStatus {
+        if let (CircuitState::Open, Some(until)) = (self.status.state, self.status.open_until) {
+            if until <= Utc::now() {
+                self.status.state = CircuitState::HalfOpen;
+            }
+        }
+        self.status.clone()
+    }
+
+    fn check(&mut self) -> Result<(), ControlClientError> {
+        match self.status() {
+            CircuitStatus {
+                state: CircuitState::Open,
+                open_until: Some(until),
+                ..
+            } => Err(ControlClientError::CircuitOpen { until }),
+            CircuitStatus {
+                state: CircuitState::HalfOpen,
+                ..
+            } => {
+                let now = Utc::now();
+                match self.trial_started.map(|started| started + self.cooldown) {
+                    Some(until) if until > now => Err(ControlClientError::CircuitOpen { until }),
+                    _ => {
+                        self.trial_started = Some(now);
+                        Ok(())
+                    }
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn on_success(&mut self) {
+        self.status = CircuitStatus::default();
+        self.trial_started = None;
+    }
+
+    fn on_failure(&mut self) {
+        self.trial_started = None;
+        self.status.consecutive_failures += 1;
+        if self.status.state == CircuitState::HalfOpen
+            || self.status.consecutive_failures >= self.threshold
+        {
+            let until = Utc::now() + self.cooldown;
+            if self.status.state != CircuitState::Open {
+                tracing::warn!(%until, "control browser circuit opened");
+            }
+            self.status.state = CircuitState::Open;
+            self.status.open_until = Some(until);
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct ControlClient {
     base_url: String,
     http: reqwest::Client,
+    signer: Option<RequestSigner>,
+    probe_timeout: StdDuration,
+    health_interval: StdDuration,
+    health: Arc<RwLock<ControlHealth>>,
+    max_retries: u32,
+    retry_backoff_ms: u64,
+    breaker: Arc<Mutex<CircuitBreaker>>,
 }
 
 impl ControlClient {
@@ -21,30 +229,345 @@ impl ControlClient {
         Self {
             base_url: cfg.base_url,
             http: reqwest::Client::new(),
+            signer: cfg.signing_secret.as_deref().map(RequestSigner::new),
+            probe_timeout: StdDuration::from_millis(cfg.probe_timeout_ms),
+            health_interval: StdDuration::from_secs(cfg.health_interval_secs.max(1)),
+            health: Arc::new(RwLock::new(ControlHealth::default())),
+            max_retries: cfg.max_retries,
+            retry_backoff_ms: cfg.retry_backoff_ms,
+            breaker: Arc::new(Mutex::new(CircuitBreaker {
+                threshold: cfg.breaker_threshold.max(1),
+                cooldown: Duration::seconds(cfg.breaker_cooldown_secs as i64),
+                status: CircuitStatus::default(),
+                trial_started: None,
+            })),
+        }
+    }
+
+    pub fn health(&self) -> ControlHealth {
+        ControlHealth {
+            circuit: self.circuit(),
+            ..self.health.read().clone()
+        }
+    }
+
+    pub fn circuit(&self) -> CircuitStatus {
+        self.breaker.lock().status()
+    }
+
+    pub fn health_interval(&self) -> StdDuration {
+        self.health_interval
+    }
+
+    /// Whether commands are worth sending; false until a probe has succeeded
+    /// and while the circuit is open.
+    pub fn is_available(&self) -> bool {
+        self.health.read().reachable && self.circuit().state != CircuitState::Open
+    }
+
+    /// Requests the control page and records reachability and latency.
+    pub async fn probe(&self) -> ControlHealth {
+        let started = Instant::now();
+        let result = match self.endpoint("/", &[]) {
+            Ok(url) => self
+                .get(url)
+                .timeout(self.probe_timeout)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+                .map(|resp| {
+                    resp.headers()
+                        .get(BOOT_ID_HEADER)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string)
+                })
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        // The breaker is left to command outcomes: the page may be served
+        // while commands still fail.
+        let mut health = self.health.write();
+        health.last_checked = Some(Utc::now());
+        match result {
+            Ok(boot_id) => {
+                health.reachable = true;
+                health.latency_ms = Some(started.elapsed().as_millis() as u64);
+                health.last_error = None;
+                health.boot_id = boot_id;
+            }
+            Err(e) => {
+                health.reachable = false;
+                health.latency_ms = None;
+                health.last_error = Some(e);
+            }
+        }
+        ControlHealth {
+            circuit: self.circuit(),
+            ..health.clone()
         }
     }
 
+    /// Loads an authorization URL in the account's pane.
     pub async fn open_auth(
         &self,
         account_id: u32,
         auth_url: &str,
     ) -> Result<(), ControlClientError> {
+        self.send(
+            "/open-auth",
+            &[
+                ("account_id", account_id.to_string()),
+                ("auth_url", auth_url.to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// Loads an arbitrary URL in the account's pane.
+    #[allow(dead_code)] // typed wrapper for tooling; no route needs it yet
+    pub async fn open_url(
+        &self,
+        account_id: u32,
+        target_url: &str,
+    ) -> Result<(), ControlClientError> {
+        self.send(
+            "/open-url",
+            &[
+                ("account_id", account_id.to_string()),
+                ("target_url", target_url.to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// Loads a URL in the browser's own verification pane. The router opens
+    /// verification flows in its leased pane with `open_auth` instead.
+    #[allow(dead_code)] // typed wrapper for tooling; no route needs it yet
+    pub async fn open_verification(&self, target_url: &str) -> Result<(), ControlClientError> {
+        self.send(
+            "/open-verification",
+            &[("target_url", target_url.to_string())],
+        )
+        .await
+    }
+
+    #[allow(dead_code)] // typed wrapper for tooling; no route needs it yet
+    pub async fn navigate(
+        &self,
+        account_id: u32,
+        action: NavigateAction,
+    ) -> Result<(), ControlClientError> {
+        self.send(
+            "/navigate",
+            &[
+                ("account_id", account_id.to_string()),
+                ("action", action.as_str().to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// Shows a countdown overlay on the pane that alerts at `deadline`.
+    pub async fn set_timer(
+        &self,
+        account_id: u32,
+        deadline: TimerDeadline,
+    ) -> Result<(), ControlClientError> {
+        self.send(
+            "/set-timer",
+            &[
+                ("account_id", account_id.to_string()),
+                (
+                    "target_time",
+                    deadline.at().to_rfc3339_opts(SecondsFormat::Millis, true),
+                ),
+            ],
+        )
+        .await
+    }
+
+    pub async fn cancel_timer(&self, account_id: u32) -> Result<(), ControlClientError> {
+        self.send("/cancel-timer", &[("account_id", account_id.to_string())])
+            .await
+    }
+
+    /// GET builder carrying the request signature when a secret is configured.
+    fn get(&self, url: Url) -> reqwest::RequestBuilder {
+        let signature = self.signer.as_ref().map(|s| s.sign("GET", &url));
+        let builder = self.http.get(url);
+        match signature {
+            Some(signature) => signature.apply(builder),
+            None => builder,
+        }
+    }
+
+    fn endpoint(&self, path: &str, params: &[(&str, String)]) -> Result<Url, ControlClientError> {
         let mut url =
             Url::parse(&self.base_url).map_err(|e| ControlClientError::Url(e.to_string()))?;
+        url.set_path(path);
+        url.query_pairs_mut().extend_pairs(params);
+        Ok(url)
+    }
 
-        url.set_path("/open-auth");
-        url.query_pairs_mut()
-            .append_pair("account_id", &account_id.to_string())
-            .append_pair("auth_url", auth_url);
+    /// Sends a command, retrying connection failures, timeouts and 5xx
+    /// with jittered backoff, and feeding the outcome to the circuit breaker.
+    async fn send(&self, path: &str, params: &[(&str, String)]) -> Result<(), ControlClientError> {
+        let url = self.endpoint(path, params)?;
+        self.breaker.lock().check()?;
 
-        self.http
-            .get(url)
-            .send()
+        let mut attempt = 0;
+        let result = loop {
+            attempt += 1;
+            let outcome = self.send_once(url.clone()).await;
+            let transient = matches!(
+                &outcome,
+                Err(ControlClientError::Unavailable(_))
+                    | Err(ControlClientError::Rejected { status: 500.., .. })
+            );
+            if !transient || attempt > self.max_retries {
+                break outcome;
+            }
+            tokio::time::sleep(backoff_delay(self.retry_backoff_ms, attempt)).await;
+        };
+
+        let mut breaker = self.breaker.lock();
+        match &result {
+            Err(ControlClientError::Unavailable(_))
+            | Err(ControlClientError::Rejected { status: 500.., .. }) => breaker.on_failure(),
+            // Any other answer means the server is alive.
+            _ => breaker.on_success(),
+        }
+        result
+    }
+
+    async fn send_once(&self, url: Url) -> Result<(), ControlClientError> {
+        let resp = self.get(url).send().await.map_err(|e| {
+            if e.is_connect() || e.is_timeout() {
+                self.mark_unreachable(e.to_string());
+                ControlClientError::Unavailable(e.to_string())
+            } else {
+                ControlClientError::Http(e.to_string())
+            }
+        })?;
+
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        // The control server reports failures as `{"status": "<reason>"}`.
+        let reason = resp
+            .json::<ControlResponse>()
             .await
-            .map_err(|e| ControlClientError::Http(e.to_string()))?
-            .error_for_status()
-            .map_err(|e| ControlClientError::Http(e.to_string()))?;
+            .map(|body| body.status)
+            .unwrap_or_else(|_| status.to_string());
+        Err(ControlClientError::Rejected {
+            status: status.as_u16(),
+            reason,
+        })
+    }
+
+    /// Lets a failed command flip the health state before the next probe.
+    fn mark_unreachable(&self, error: String) {
+        let mut health = self.health.write();
+        health.reachable = false;
+        health.latency_ms = None;
+        health.last_checked = Some(Utc::now());
+        health.last_error = Some(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(threshold: u32) -> CircuitBreaker {
+        CircuitBreaker {
+            threshold,
+            cooldown: Duration::seconds(30),
+            status: CircuitStatus::default(),
+            trial_started: None,
+        }
+    }
+
+    /// Opens the breaker and moves its cooldown into the past.
+    fn cooled_down(threshold: u32) -> CircuitBreaker {
+        let mut breaker = breaker(threshold);
+        for _ in 0..threshold {
+            breaker.on_failure();
+        }
+        breaker.status.open_until = Some(Utc::now() - Duration::seconds(1));
+        breaker
+    }
+
+    #[test]
+    fn opens_after_threshold_failures() {
+        let mut breaker = breaker(3);
+        breaker.on_failure();
+        breaker.on_failure();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+        assert!(breaker.check().is_ok());
+
+        breaker.on_failure();
+        assert_eq!(breaker.status().state, CircuitState::Open);
+        assert!(matches!(
+            breaker.check(),
+            Err(ControlClientError::CircuitOpen { .. })
+        ));
+    }
+
+    #[test]
+    fn success_resets_the_failure_count() {
+        let mut breaker = breaker(2);
+        breaker.on_failure();
+        breaker.on_success();
+        breaker.on_failure();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+    }
+
+    #[test]
+    fn half_opens_after_the_cooldown() {
+        let mut breaker = cooled_down(2);
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn half_open_admits_a_single_trial() {
+        let mut breaker = cooled_down(2);
+        assert!(breaker.check().is_ok());
+        assert!(matches!(
+            breaker.check(),
+            Err(ControlClientError::CircuitOpen { .. })
+        ));
+
+        // A trial whose outcome never arrives stops blocking after a cooldown.
+        breaker.trial_started = Some(Utc::now() - Duration::seconds(31));
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn successful_trial_closes_the_circuit() {
+        let mut breaker = cooled_down(2);
+        assert!(breaker.check().is_ok());
+        breaker.on_success();
+
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Closed);
+        assert_eq!(status.consecutive_failures, 0);
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn failed_trial_reopens_the_circuit() {
+        let mut breaker = cooled_down(5);
+        assert!(breaker.check().is_ok());
+        breaker.on_failure();
 
-        Ok(())
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Open);
+        assert!(status.open_until.is_some_and(|until| until > Utc::now()));
+        assert!(breaker.check().is_err());
     }
 }
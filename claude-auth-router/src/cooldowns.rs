@@ -0,0 +1,159 @@
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock, RwLockWriteGuard};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// An account that hit a usage limit and should not get work until
+/// `available_at`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cooldown {
+    pub available_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub set_at: DateTime<Utc>,
+}
+
+/// Per-account cooldown schedule, optionally persisted to a JSON file so it
+/// survives restarts of the router.
+#[derive(Clone, Default)]
+pub struct AccountCooldowns {
+    inner: Arc<RwLock<HashMap<u32, Cooldown>>>,
+    file: Option<Arc<ScheduleFile>>,
+}
+
+/// Where the schedule is written. Snapshots are numbered so a write that
+/// finishes late never replaces a newer one.
+struct ScheduleFile {
+    path: PathBuf,
+    version: AtomicU64,
+    written: Mutex<u64>,
+}
+
+impl AccountCooldowns {
+    /// Loads the schedule from `path` when it exists; later changes are
+    /// written back to it.
+    pub fn load(path: Option<&str>) -> Self {
+        let Some(path) = path.map(PathBuf::from) else {
+            return Self::default();
+        };
+
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                tracing::warn!(path = %path.display(), error = %e, "ignoring unreadable timer schedule");
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "ignoring unreadable timer schedule");
+                HashMap::new()
+            }
+        };
+
+        Self {
+            inner: Arc::new(RwLock::new(entries)),
+            file: Some(Arc::new(ScheduleFile {
+                path,
+                version: AtomicU64::new(0),
+                written: Mutex::new(0),
+            })),
+        }
+    }
+
+    pub fn set(
+        &self,
+        account_id: u32,
+        available_at: DateTime<Utc>,
+        reason: Option<String>,
+    ) -> Cooldown {
+        let cooldown = Cooldown {
+            available_at,
+            reason,
+            set_at: Utc::now(),
+        };
+        let mut entries = self.inner.write();
+        entries.insert(account_id, cooldown.clone());
+        self.persist(entries);
+        cooldown
+    }
+
+    /// Returns the cleared record, if one was still active.
+    pub fn clear(&self, account_id: u32) -> Option<Cooldown> {
+        let mut entries = self.inner.write();
+        let removed = entries.remove(&account_id);
+        if removed.is_some() {
+            self.persist(entries);
+        }
+        removed.filter(|c| c.available_at > Utc::now())
+    }
+
+    /// Active cooldown for the account; lapsed ones are ignored.
+    pub fn get(&self, account_id: u32) -> Option<Cooldown> {
+        self.inner
+            .read()
+            .get(&account_id)
+            .filter(|c| c.available_at > Utc::now())
+            .cloned()
+    }
+
+    /// All cooldowns still in effect.
+    pub fn active(&self) -> Vec<(u32, Cooldown)> {
+        let now = Utc::now();
+        self.inner
+            .read()
+            .iter()
+            .filter(|(_, c)| c.available_at > now)
+            .map(|(id, c)| (*id, c.clone()))
+            .collect()
+    }
+
+    /// Removes and returns cooldowns whose deadline has passed.
+    pub fn take_due(&self) -> Vec<(u32, Cooldown)> {
+        let now = Utc::now();
+        let mut entries = self.inner.write();
+        let due: Vec<u32> = entries
+            .iter()
+            .filter(|(_, c)| c.available_at <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        if due.is_empty() {
+            return Vec::new();
+        }
+
+        let fired = due
+            .into_iter()
+            .filter_map(|id| entries.remove(&id).map(|c| (id, c)))
+            .collect();
+        self.persist(entries);
+        fired
+    }
+
+    /// Snapshots the schedule, releases the lock, and writes the snapshot on
+    /// the blocking pool.
+    fn persist(&self, entries: RwLockWriteGuard<'_, HashMap<u32, Cooldown>>) {
+        let Some(file) = self.file.clone() else {
+            return;
+        };
+        let snapshot = serde_json::to_string_pretty(&*entries);
+        let version = file.version.fetch_add(1, Ordering::SeqCst) + 1;
+        drop(entries);
+
+        tokio::task::spawn_blocking(move || {
+            let mut written = file.written.lock();
+            if *written > version {
+                return;
+            }
+            let result = snapshot
+                .map_err(|e| e.to_string())
+                .and_then(|data| std::fs::write(&file.path, data).map_err(|e| e.to_string()));
+            match result {
+                Ok(()) => *written = version,
+                Err(e) => {
+                    tracing::warn!(path = %file.path.display(), error = %e, "failed to persist timer schedule")
+                }
+            }
+        });
+    }
+}
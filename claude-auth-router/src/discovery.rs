@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::oauth::OAuthError;
+
+const WELL_KNOWN_PATH: &str = ".well-known/openid-configuration";
+
+/// Subset of the OpenID Provider Metadata the router cares about.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    #[serde(default)]
+    pub revocation_endpoint: Option<String>,
+    #[serde(default)]
+    pub introspection_endpoint: Option<String>,
+    #[serde(default)]
+    pub userinfo_endpoint: Option<String>,
+    #[serde(default)]
+    pub jwks_uri: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedMetadata {
+    pub metadata: ProviderMetadata,
+    pub fetched_at: DateTime<Utc>,
+}
+
+pub fn well_known_url(issuer: &str) -> String {
+    format!("{}/{}", issuer.trim_end_matches('/'), WELL_KNOWN_PATH)
+}
+
+pub async fn fetch_metadata(
+    http: &reqwest::Client,
+    issuer: &str,
+) -> Result<ProviderMetadata, OAuthError> {
+    let metadata: ProviderMetadata = http
+        .get(well_known_url(issuer))
+        .send()
+        .await
+        .map_err(|e| OAuthError::Http(e.to_string()))?
+        .error_for_status()
+        .map_err(|e| OAuthError::Discovery(e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| OAuthError::Discovery(e.to_string()))?;
+
+    // OIDC Discovery 1.0 §4.3: the returned issuer must match the one we asked for.
+    if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+        return Err(OAuthError::Discovery(format!(
+            "issuer mismatch: expected {issuer}, got {}",
+            metadata.issuer
+        )));
+    }
+
+    Ok(metadata)
+}
@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::accounts::AccountRegistry;
+use crate::control_client::{ControlClient, ControlClientError};
+
+#[derive(Debug, Error)]
+pub enum DriverError {
+    #[error(transparent)]
+    Control(#[from] ControlClientError),
+    #[error("failed to launch {program}: {error}")]
+    Launch { program: String, error: String },
+}
+
+pub type DriverFuture<'a> = Pin<Box<dyn Future<Output = Result<(), DriverError>> + Send + 'a>>;
+
+/// Something that can put an authorization URL in front of the user for a
+/// given account.
+pub trait BrowserDriver: Send + Sync {
+    fn open_auth<'a>(&'a self, account_id: u32, auth_url: &'a str) -> DriverFuture<'a>;
+
+    /// Opens a verification flow; drivers without a dedicated verification
+    /// pane treat it like any other flow for the account.
+    fn open_verification<'a>(&'a self, account_id: u32, url: &'a str) -> DriverFuture<'a> {
+        self.open_auth(account_id, url)
+    }
+
+    /// Whether it is worth trying to open URLs right now.
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    /// The URL is only recorded on the request for the user to open.
+    fn records_only(&self) -> bool {
+        false
+    }
+
+    /// Opening URLs goes through the Electron control browser, so the router
+    /// is not ready while it is down.
+    fn needs_control_browser(&self) -> bool {
+        false
+    }
+}
+
+/// Per-account driver choice in the account registry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DriverConfig {
+    /// Load the URL in the account's pane of the Electron control browser.
+    ControlBrowser,
+    /// Run a local program; `{url}` and `{account_id}` in `args` are
+    /// substituted, e.g. `["--profile-directory=Profile {account_id}", "{url}"]`.
+    Command {
+        program: String,
+        #[serde(default = "default_command_args")]
+        args: Vec<String>,
+    },
+    /// Only record the URL on the request, for status polling to show.
+    Print,
+}
+
+fn default_command_args() -> Vec<String> {
+    vec!["{url}".into()]
+}
+
+impl FromStr for DriverConfig {
+    type Err = String;
+
+    /// Parses the global default: `control_browser`, `print`, or
+    /// `command:<program>` (which receives the URL as its only argument).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "control_browser" => Ok(Self::ControlBrowser),
+            "print" => Ok(Self::Print),
+            other => match other.strip_prefix("command:") {
+                Some(program) if !program.trim().is_empty() => Ok(Self::Command {
+                    program: program.trim().to_string(),
+                    args: default_command_args(),
+                }),
+                _ => Err(format!("unsupported browser driver: {other}")),
+            },
+        }
+    }
+}
+
+impl BrowserDriver for ControlClient {
+    fn open_auth<'a>(&'a self, account_id: u32, auth_url: &'a str) -> DriverFuture<'a> {
+        Box::pin(async move {
+            ControlClient::open_auth(self, account_id, auth_url)
+                .await
+                .map_err(DriverError::from)
+        })
+    }
+
+    // Verification flows keep the default and go to `/open-auth` for the
+    // pane the router leased; `/open-verification` would use the browser's
+    // own `VERIFICATION_VIEW_ID`, which may differ from the router's.
+
+    fn is_available(&self) -> bool {
+        ControlClient::is_available(self)
+    }
+
+    fn needs_control_browser(&self) -> bool {
+        true
+    }
+}
+
+pub struct CommandDriver {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl BrowserDriver for CommandDriver {
+    fn open_auth<'a>(&'a self, account_id: u32, auth_url: &'a str) -> DriverFuture<'a> {
+        Box::pin(async move {
+            let args = self.args.iter().map(|arg| {
+                arg.replace("{account_id}", &account_id.to_string())
+                    .replace("{url}", auth_url)
+            });
+            // The browser may outlive the flow; tokio reaps it on exit.
+            tokio::process::Command::new(&self.program)
+                .args(args)
+                .stdin(std::process::Stdio::null())
+                .spawn()
+                .map(|_| ())
+                .map_err(|e| DriverError::Launch {
+                    program: self.program.clone(),
+                    error: e.to_string(),
+                })
+        })
+    }
+}
+
+pub struct PrintDriver;
+
+impl BrowserDriver for PrintDriver {
+    fn open_auth<'a>(&'a self, account_id: u32, auth_url: &'a str) -> DriverFuture<'a> {
+        Box::pin(async move {
+            tracing::info!(account_id, %auth_url, "open this URL to continue");
+            Ok(())
+        })
+    }
+
+    fn records_only(&self) -> bool {
+        true
+    }
+}
+
+/// Driver for each account, falling back to the default for unknown ids.
+#[derive(Clone)]
+pub struct BrowserDrivers {
+    by_account: HashMap<u32, Arc<dyn BrowserDriver>>,
+    default: Arc<dyn BrowserDriver>,
+}
+
+impl BrowserDrivers {
+    pub fn new(
+        accounts: &AccountRegistry,
+        default: &DriverConfig,
+        control: &ControlClient,
+    ) -> Self {
+        let build = |cfg: &DriverConfig| -> Arc<dyn BrowserDriver> {
+            match cfg {
+                DriverConfig::ControlBrowser => Arc::new(control.clone()),
+                DriverConfig::Command { program, args } => Arc::new(CommandDriver {
+                    program: program.clone(),
+                    args: args.clone(),
+                }),
+                DriverConfig::Print => Arc::new(PrintDriver),
+            }
+        };
+
+        Self::from_drivers(
+            accounts
+                .all()
+                .iter()
+                .map(|a| (a.id, build(a.driver.as_ref().unwrap_or(default))))
+                .collect(),
+            build(default),
+        )
+    }
+
+    /// Uses the given drivers as they are, e.g. a fake one in tests.
+    pub fn from_drivers(
+        by_account: HashMap<u32, Arc<dyn BrowserDriver>>,
+        default: Arc<dyn BrowserDriver>,
+    ) -> Self {
+        Self {
+            by_account,
+            default,
+        }
+    }
+
+    /// Whether any account opens its URLs through the control browser; the
+    /// default only counts when no account has a driver of its own.
+    pub fn needs_control_browser(&self) -> bool {
+        if self.by_account.is_empty() {
+            return self.default.needs_control_browser();
+        }
+        self.by_account.values().any(|d| d.needs_control_browser())
+    }
+
+    pub fn for_account(&self, account_id: u32) -> &dyn BrowserDriver {
+        self.by_account
+            .get(&account_id)
+            .unwrap_or(&self.default)
+            .as_ref()
+    }
+}
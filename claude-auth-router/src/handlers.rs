@@ -1,24 +1,55 @@
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
-    Json,
+    http::{header, HeaderMap, StatusCode},
+    response::Html,
+    Form, Json,
 };
+use base64::Engine;
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use std::{collections::HashMap, sync::Arc};
+use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::{
-    control_client::ControlClient,
-    models::{AuthRequest, RequestStatus, TokenBundle},
-    oauth::OAuthClient,
+    accounts::{Account, AccountRegistry},
+    config::{CallbackPageConfig, IntrospectionConfig},
+    control_client::{
+        CircuitStatus, ControlClient, ControlClientError, ControlHealth, TimerDeadline,
+    },
+    cooldowns::{AccountCooldowns, Cooldown},
+    drivers::{BrowserDrivers, DriverError},
+    id_token::VerifiedIdentity,
+    leases::{AccountLeases, Lease},
+    models::{AuthRequest, RequestStatus, TokenBundle, TokenKind},
+    oauth::{
+        OAuthError, OAuthProviders, ProviderError, TokenExchangeParams, ACCESS_TOKEN_TYPE,
+        TOKEN_EXCHANGE_GRANT,
+    },
+    pages::{self, PageBehavior},
+    selection::{AccountChoice, AccountSelector},
+    signing::RequestVerifier,
     store::AuthStore,
 };
 
 #[derive(Clone)]
 pub struct AppState {
     pub store: AuthStore,
-    pub oauth: OAuthClient,
+    pub oauth: OAuthProviders,
     pub control: ControlClient,
+    pub accounts: Arc<AccountRegistry>,
+    pub leases: AccountLeases,
+    pub selector: AccountSelector,
+    pub cooldowns: AccountCooldowns,
+    /// How each account's authorization URLs are opened.
+    pub drivers: BrowserDrivers,
+    /// Pane that verification flows are routed to.
+    pub verification_account: u32,
+    /// Authenticates pane events; they are refused when no secret is set.
+    pub pane_events: Option<RequestVerifier>,
+    pub introspection: Arc<IntrospectionConfig>,
+    pub callback_page: Arc<CallbackPageConfig>,
+    /// Client names allowed the client_credentials grant, with their secrets.
+    pub service_clients: Arc<HashMap<String, String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,30 +58,222 @@ pub struct CreateTokenRequest {
     pub hostname: String,
     #[serde(default)]
     pub scopes: Vec<String>,
+    /// Configured provider name; the router's default provider when omitted.
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Use the provider's out-of-band redirect; the code is then submitted
+    /// via `/v1/token-requests/:id/authorization-code`.
+    #[serde(default)]
+    pub out_of_band: bool,
+    /// Approved request to extend: only scopes it does not already hold are
+    /// requested, and the result is merged with its token.
+    #[serde(default)]
+    pub grant_id: Option<Uuid>,
+    /// Open the flow right away in a pane picked by the router, as with
+    /// `account_id: "auto"` on select-account.
+    #[serde(default)]
+    pub auto_assign: bool,
+    /// Run the flow in the dedicated verification pane; implied for
+    /// providers configured as verification flows.
+    #[serde(default)]
+    pub verification: bool,
 }
 
 #[derive(Debug, Serialize)]
 pub struct CreateTokenResponse {
     pub request_id: Uuid,
     pub status: RequestStatus,
+    /// Set when the request completed immediately (client_credentials).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<TokenBundle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Pane picked for `auto_assign` and verification requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<u32>,
 }
 
 pub async fn create_token_request(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(body): Json<CreateTokenRequest>,
 ) -> (StatusCode, Json<CreateTokenResponse>) {
-    let req = AuthRequest::new(body.client_name, body.hostname, body.scopes);
+    let reject = |error: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(CreateTokenResponse {
+                request_id: Uuid::nil(),
+                status: RequestStatus::Error,
+                token: None,
+                error: Some(error),
+                account_id: None,
+            }),
+        )
+    };
+
+    let base = match body.grant_id {
+        Some(grant_id) => match state.store.get(&grant_id) {
+            Some(base)
+                if base.status == RequestStatus::Approved
+                    && base.client_name == body.client_name =>
+            {
+                Some(base)
+            }
+            _ => return reject("invalid_grant_id".into()),
+        },
+        None => None,
+    };
+
+    let provider = body
+        .provider
+        .or_else(|| base.as_ref().map(|b| b.provider.clone()))
+        .unwrap_or_else(|| state.oauth.default_name().to_string());
+    if base.as_ref().is_some_and(|b| b.provider != provider) {
+        return reject("grant_provider_mismatch".into());
+    }
+    if let Err(e) = state
+        .oauth
+        .client(&provider)
+        .and_then(|oauth| oauth.redirect_uri(body.out_of_band).map(|_| ()))
+    {
+        return reject(e.to_string());
+    }
+
+    let mut req = AuthRequest::new(body.client_name, body.hostname, body.scopes, provider);
+    req.out_of_band = body.out_of_band;
+    req.verification = body.verification
+        || state
+            .oauth
+            .get(&req.provider)
+            .is_some_and(|oauth| oauth.is_verification_flow());
+
+    if state.service_clients.contains_key(&req.client_name) {
+        return issue_client_credentials(&state, &headers, req).await;
+    }
+
+    if let Some(base) = base {
+        let held = base.granted_scopes();
+        let requested = std::mem::take(&mut req.scopes);
+        let delta: Vec<String> = requested
+            .iter()
+            .filter(|s| !held.contains(s))
+            .cloned()
+            .collect();
+        if delta.is_empty() {
+            // Nothing new to consent to; hand back the existing grant.
+            return (
+                StatusCode::OK,
+                Json(CreateTokenResponse {
+                    request_id: base.id,
+                    status: base.status,
+                    token: base.token,
+                    error: None,
+                    account_id: None,
+                }),
+            );
+        }
+        // Only ask for the delta when the provider folds the existing grant
+        // into the new token; otherwise the token would lack the held scopes.
+        req.scopes = if incremental(&state, &req) {
+            delta
+        } else {
+            let mut union = held;
+            union.extend(delta);
+            union
+        };
+        req.base_grant = Some(base.id);
+    }
+
     let status = req.status.clone();
     let id = req.id;
-    state.store.insert(req);
+    let open_now = body.auto_assign || req.verification;
+    state.store.insert(req.clone());
 
     info!(%id, "created token request");
 
+    if open_now {
+        // The request exists either way; a failed assignment leaves it
+        // pending for a manual select-account. Verification requests are
+        // routed to their pane regardless of the choice.
+        let (_, Json(selected)) = open_in_account(&state, req, AccountChoice::AUTO).await;
+        return (
+            StatusCode::ACCEPTED,
+            Json(CreateTokenResponse {
+                request_id: id,
+                status: selected.status,
+                token: None,
+                error: selected.error,
+                account_id: selected.account_id,
+            }),
+        );
+    }
+
     (
         StatusCode::ACCEPTED,
         Json(CreateTokenResponse {
             request_id: id,
             status,
+            token: None,
+            error: None,
+            account_id: None,
+        }),
+    )
+}
+
+/// Completes a request for a configured service client on the spot using the
+/// client_credentials grant; no pane or human is involved.
+async fn issue_client_credentials(
+    state: &AppState,
+    headers: &HeaderMap,
+    mut req: AuthRequest,
+) -> (StatusCode, Json<CreateTokenResponse>) {
+    if authenticate_caller(&state.service_clients, headers).as_deref() != Some(&req.client_name) {
+        warn!(client = %req.client_name, "service client failed authentication");
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(CreateTokenResponse {
+                request_id: Uuid::nil(),
+                status: RequestStatus::Error,
+                token: None,
+                error: Some("invalid_client".into()),
+                account_id: None,
+            }),
+        );
+    }
+
+    state.store.insert(req.clone());
+    info!(id = %req.id, client = %req.client_name, "issuing client_credentials token");
+
+    let result = match state.oauth.client(&req.provider) {
+        Ok(oauth) => oauth
+            .client_credentials(&req.scopes, &mut req.exchange_attempts)
+            .await
+            .and_then(|token| {
+                let missing = oauth.verify_scopes(&req.scopes, &token)?;
+                Ok((token, missing))
+            }),
+        Err(e) => Err(e),
+    };
+
+    let status = match result {
+        Ok((token, missing)) => {
+            approve_request(state, &mut req, token, missing);
+            StatusCode::OK
+        }
+        Err(e) => {
+            fail_request(state, &mut req, &e);
+            StatusCode::BAD_GATEWAY
+        }
+    };
+
+    (
+        status,
+        Json(CreateTokenResponse {
+            request_id: req.id,
+            status: req.status,
+            token: req.token,
+            error: req.error,
+            account_id: None,
         }),
     )
 }
@@ -62,58 +285,136 @@ pub struct StatusResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<TokenBundle>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_scopes: Vec<String>,
+    /// Pane the flow runs in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<u32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub verification: bool,
+    /// Authorization URL for the user to open, with the `print` driver.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_url: Option<String>,
+    /// Latest page and login email reported by the control browser.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pane_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pane_email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
 
+impl StatusResponse {
+    fn error(request_id: Uuid, error: String) -> Self {
+        Self {
+            request_id,
+            status: RequestStatus::Error,
+            token: None,
+            subject: None,
+            email: None,
+            partial: false,
+            missing_scopes: Vec::new(),
+            account_id: None,
+            verification: false,
+            auth_url: None,
+            pane_url: None,
+            pane_email: None,
+            error: Some(error),
+        }
+    }
+
+    /// Reports a rejected call without changing the request's own status.
+    fn with_error(mut self, error: &str) -> Self {
+        self.error = Some(error.into());
+        self
+    }
+}
+
+impl From<AuthRequest> for StatusResponse {
+    fn from(req: AuthRequest) -> Self {
+        Self {
+            request_id: req.id,
+            status: req.status,
+            token: req.token,
+            subject: req.subject,
+            email: req.email,
+            partial: req.partial,
+            missing_scopes: req.missing_scopes,
+            account_id: req.account_id,
+            verification: req.verification,
+            auth_url: req.auth_url,
+            pane_url: req.pane_url,
+            pane_email: req.pane_email,
+            error: req.error,
+        }
+    }
+}
+
 pub async fn get_status(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> (StatusCode, Json<StatusResponse>) {
     match parse_uuid(&id) {
         Ok(uuid) => match state.store.get(&uuid) {
-            Some(req) => (
-                StatusCode::OK,
-                Json(StatusResponse {
-                    request_id: req.id,
-                    status: req.status,
-                    token: req.token,
-                    error: req.error,
-                }),
-            ),
+            Some(req) => (StatusCode::OK, Json(StatusResponse::from(req))),
             None => (
                 StatusCode::NOT_FOUND,
-                Json(StatusResponse {
-                    request_id: uuid,
-                    status: RequestStatus::Error,
-                    token: None,
-                    error: Some("request_not_found".into()),
-                }),
+                Json(StatusResponse::error(uuid, "request_not_found".into())),
             ),
         },
         Err(e) => (
             StatusCode::BAD_REQUEST,
-            Json(StatusResponse {
-                request_id: Uuid::nil(),
-                status: RequestStatus::Error,
-                token: None,
-                error: Some(format!("invalid_request_id: {e}")),
-            }),
+            Json(StatusResponse::error(
+                Uuid::nil(),
+                format!("invalid_request_id: {e}"),
+            )),
         ),
     }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SelectAccountRequest {
-    pub account_id: u32,
+    /// Pane id, or `"auto"` to let the router pick one.
+    pub account_id: AccountChoice,
 }
 
 #[derive(Debug, Serialize)]
 pub struct SelectAccountResponse {
     pub request_id: Uuid,
     pub status: RequestStatus,
-    pub account_id: u32,
+    /// Pane the flow was opened in; absent when auto-assignment found none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<u32>,
+    /// Authorization URL for the user to open, with the `print` driver.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// When the pane's current lease lapses, for `account_busy` rejections.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub busy_until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Control-browser circuit breaker state, for `control_browser_unavailable`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit: Option<CircuitStatus>,
+}
+
+impl SelectAccountResponse {
+    fn rejected(req: &AuthRequest, account_id: Option<u32>, error: &str) -> Self {
+        Self {
+            request_id: req.id,
+            status: req.status.clone(),
+            account_id,
+            auth_url: None,
+            error: Some(error.into()),
+            busy_until: None,
+            circuit: None,
+        }
+    }
 }
 
 pub async fn select_account(
@@ -123,46 +424,17 @@ pub async fn select_account(
 ) -> (StatusCode, Json<SelectAccountResponse>) {
     match parse_uuid(&id) {
         Ok(uuid) => match state.store.get(&uuid) {
-            Some(mut req) => {
-                req.status = RequestStatus::InProgress;
-                req.account_id = Some(body.account_id);
-                req.updated_at = chrono::Utc::now();
-
-                let result = match state.oauth.build_auth_url(&req.id, &req.scopes) {
-                    Ok(url) => {
-                        state
-                            .control
-                            .open_auth(body.account_id, &url)
-                            .await
-                            .map_err(|e| crate::oauth::OAuthError::Exchange(e.to_string()))
-                            .map(|_| url)
-                    }
-                    Err(err) => Err(err),
-                };
-
-                if let Err(err) = result {
-                    req.status = RequestStatus::Error;
-                    req.error = Some(err.to_string());
-                }
-
-                state.store.update(req.clone());
-                (
-                    StatusCode::OK,
-                    Json(SelectAccountResponse {
-                        request_id: req.id,
-                        status: req.status,
-                        account_id: body.account_id,
-                        error: req.error,
-                    }),
-                )
-            }
+            Some(req) => open_in_account(&state, req, body.account_id).await,
             None => (
                 StatusCode::NOT_FOUND,
                 Json(SelectAccountResponse {
                     request_id: uuid,
                     status: RequestStatus::Error,
-                    account_id: body.account_id,
+                    account_id: body.account_id.id(),
+                    auth_url: None,
                     error: Some("request_not_found".into()),
+                    busy_until: None,
+                    circuit: None,
                 }),
             ),
         },
@@ -171,52 +443,1209 @@ pub async fn select_account(
             Json(SelectAccountResponse {
                 request_id: Uuid::nil(),
                 status: RequestStatus::Error,
-                account_id: body.account_id,
+                account_id: body.account_id.id(),
+                auth_url: None,
                 error: Some(format!("invalid_request_id: {e}")),
+                busy_until: None,
+                circuit: None,
             }),
         ),
     }
 }
 
+/// Leases a pane for the request and loads its authorization URL there.
+async fn open_in_account(
+    state: &AppState,
+    mut req: AuthRequest,
+    choice: AccountChoice,
+) -> (StatusCode, Json<SelectAccountResponse>) {
+    if req.status.is_terminal() {
+        return (
+            StatusCode::CONFLICT,
+            Json(SelectAccountResponse::rejected(
+                &req,
+                choice.id(),
+                "request_already_completed",
+            )),
+        );
+    }
+
+    let account_id = match lease_account(state, &req, choice) {
+        Ok(id) => id,
+        Err(rejection) => return rejection,
+    };
+
+    req.status = RequestStatus::InProgress;
+    req.account_id = Some(account_id);
+    req.updated_at = chrono::Utc::now();
+
+    let driver = state.drivers.for_account(account_id);
+    let result = match state
+        .oauth
+        .client(&req.provider)
+        .and_then(|oauth| oauth.build_auth_url(&req))
+    {
+        Ok(url) => match if req.verification {
+            driver.open_verification(account_id, &url).await
+        } else {
+            driver.open_auth(account_id, &url).await
+        } {
+            Ok(()) => Ok(url),
+            // Leave the request as it was so it can be retried once the
+            // control browser is back.
+            Err(DriverError::Control(
+                ControlClientError::Unavailable(_) | ControlClientError::CircuitOpen { .. },
+            )) => {
+                state.leases.release(req.id);
+                let req = state.store.get(&req.id).unwrap_or(req);
+                return control_unavailable(state, &req, Some(account_id));
+            }
+            Err(e) => Err(OAuthError::Exchange(e.to_string())),
+        },
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(url) => {
+            if driver.records_only() {
+                req.auth_url = Some(url);
+            }
+            state
+                .selector
+                .record(account_id, &req.client_name, &req.hostname);
+        }
+        Err(err) => {
+            req.status = RequestStatus::Error;
+            req.error = Some(err.to_string());
+            state.leases.release(req.id);
+        }
+    }
+
+    state.store.update(req.clone());
+    (
+        StatusCode::OK,
+        Json(SelectAccountResponse {
+            request_id: req.id,
+            status: req.status,
+            account_id: Some(account_id),
+            auth_url: req.auth_url,
+            error: req.error,
+            busy_until: None,
+            circuit: None,
+        }),
+    )
+}
+
+/// Resolves the requested pane and takes its lease.
+fn lease_account(
+    state: &AppState,
+    req: &AuthRequest,
+    choice: AccountChoice,
+) -> Result<u32, (StatusCode, Json<SelectAccountResponse>)> {
+    let choice = if req.verification {
+        AccountChoice::Id(state.verification_account)
+    } else {
+        choice
+    };
+    let account_id = match choice {
+        AccountChoice::Id(id) => id,
+        AccountChoice::Keyword(_) => {
+            return auto_assign(state, req).map_err(|none| match none {
+                NoAccount::DriverUnavailable => control_unavailable(state, req, None),
+                NoAccount::NoneFree => (
+                    StatusCode::CONFLICT,
+                    Json(SelectAccountResponse::rejected(
+                        req,
+                        None,
+                        "no_account_available",
+                    )),
+                ),
+            });
+        }
+    };
+
+    let error = match state.accounts.get(account_id) {
+        None => Some("unknown_account"),
+        Some(account) if !account.accepts_provider(&req.provider) => {
+            Some("account_provider_mismatch")
+        }
+        Some(_) => None,
+    };
+    if let Some(error) = error {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(SelectAccountResponse::rejected(
+                req,
+                Some(account_id),
+                error,
+            )),
+        ));
+    }
+
+    if let Some(cooldown) = state.cooldowns.get(account_id) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(SelectAccountResponse {
+                busy_until: Some(cooldown.available_at),
+                ..SelectAccountResponse::rejected(req, Some(account_id), "account_cooling_down")
+            }),
+        ));
+    }
+
+    if !state.drivers.for_account(account_id).is_available() {
+        return Err(control_unavailable(state, req, Some(account_id)));
+    }
+
+    state
+        .leases
+        .acquire(account_id, req.id)
+        .map(|_| account_id)
+        .map_err(|held| {
+            (
+                StatusCode::CONFLICT,
+                Json(SelectAccountResponse {
+                    busy_until: Some(held.expires_at),
+                    ..SelectAccountResponse::rejected(req, Some(account_id), "account_busy")
+                }),
+            )
+        })
+}
+
+/// Why [`auto_assign`] found no pane.
+enum NoAccount {
+    /// Free panes exist, but their driver (the control browser) is down.
+    DriverUnavailable,
+    /// Every eligible pane is leased or cooling down.
+    NoneFree,
+}
+
+/// Picks and leases a free pane compatible with the request's provider.
+fn auto_assign(state: &AppState, req: &AuthRequest) -> Result<u32, NoAccount> {
+    // The verification pane is always held back: any request may ask for
+    // it with `verification: true`, and it must be free when one does.
+    let free: Vec<&Account> = state
+        .accounts
+        .all()
+        .iter()
+        .filter(|account| account.accepts_provider(&req.provider))
+        .filter(|account| account.id != state.verification_account)
+        .filter(|account| state.cooldowns.get(account.id).is_none())
+        .filter(|account| {
+            state
+                .leases
+                .holder(account.id)
+                .is_none_or(|lease| lease.request_id == req.id)
+        })
+        .collect();
+    let candidates: Vec<&Account> = free
+        .iter()
+        .copied()
+        .filter(|account| state.drivers.for_account(account.id).is_available())
+        .collect();
+    if candidates.is_empty() && !free.is_empty() {
+        return Err(NoAccount::DriverUnavailable);
+    }
+
+    state
+        .selector
+        .rank(
+            state.accounts.all(),
+            &candidates,
+            &req.client_name,
+            &req.hostname,
+        )
+        .into_iter()
+        .find(|id| state.leases.acquire(*id, req.id).is_ok())
+        .ok_or(NoAccount::NoneFree)
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountStatus {
+    #[serde(flatten)]
+    pub account: Account,
+    /// Request currently running a flow in the pane.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease: Option<Lease>,
+    /// Usage-limit cooldown still in effect.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown: Option<Cooldown>,
+}
+
+pub async fn list_accounts(State(state): State<AppState>) -> Json<Vec<AccountStatus>> {
+    Json(
+        state
+            .accounts
+            .all()
+            .iter()
+            .map(|account| AccountStatus {
+                account: account.clone(),
+                lease: state.leases.holder(account.id),
+                cooldown: state.cooldowns.get(account.id),
+            })
+            .collect(),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetCooldownRequest {
+    pub available_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CooldownResponse {
+    pub account_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown: Option<Cooldown>,
+    /// Why the control-browser countdown could not be updated; the router's
+    /// own record is kept regardless.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timer_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CooldownResponse {
+    fn error(account_id: u32, error: &str) -> Self {
+        Self {
+            account_id,
+            cooldown: None,
+            timer_error: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Marks an account as exhausted until `available_at` and shows the
+/// countdown overlay on its pane.
+pub async fn set_cooldown(
+    State(state): State<AppState>,
+    Path(account_id): Path<u32>,
+    Json(body): Json<SetCooldownRequest>,
+) -> (StatusCode, Json<CooldownResponse>) {
+    if state.accounts.get(account_id).is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(CooldownResponse::error(account_id, "unknown_account")),
+        );
+    }
+    if body.available_at <= chrono::Utc::now() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(CooldownResponse::error(account_id, "available_at_in_past")),
+        );
+    }
+
+    let cooldown = state
+        .cooldowns
+        .set(account_id, body.available_at, body.reason);
+    info!(account_id, available_at = %cooldown.available_at, "account cooling down");
+
+    // Deadlines beyond the overlay's window are still honoured router-side.
+    let timer = match TimerDeadline::new(cooldown.available_at) {
+        Ok(deadline) => state.control.set_timer(account_id, deadline).await,
+        Err(e) => Err(e),
+    };
+
+    (
+        StatusCode::OK,
+        Json(CooldownResponse {
+            account_id,
+            cooldown: Some(cooldown),
+            timer_error: timer.err().map(|e| e.to_string()),
+            error: None,
+        }),
+    )
+}
+
+pub async fn clear_cooldown(
+    State(state): State<AppState>,
+    Path(account_id): Path<u32>,
+) -> (StatusCode, Json<CooldownResponse>) {
+    if state.accounts.get(account_id).is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(CooldownResponse::error(account_id, "unknown_account")),
+        );
+    }
+
+    if state.cooldowns.clear(account_id).is_some() {
+        info!(account_id, "account cooldown cleared");
+    }
+    let timer = state.control.cancel_timer(account_id).await;
+
+    (
+        StatusCode::OK,
+        Json(CooldownResponse {
+            account_id,
+            cooldown: None,
+            timer_error: timer.err().map(|e| e.to_string()),
+            error: None,
+        }),
+    )
+}
+
+fn control_unavailable(
+    state: &AppState,
+    req: &AuthRequest,
+    account_id: Option<u32>,
+) -> (StatusCode, Json<SelectAccountResponse>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(SelectAccountResponse {
+            circuit: Some(state.control.circuit()),
+            ..SelectAccountResponse::rejected(req, account_id, "control_browser_unavailable")
+        }),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaneEventKind {
+    Navigated,
+    /// The pane was closed or its renderer went away.
+    Closed,
+    LoadFailed,
+    LoginEmailDetected,
+}
+
+/// Pane event from the control browser, sent as signed query parameters.
+#[derive(Debug, Deserialize)]
+pub struct PaneEvent {
+    pub account_id: u32,
+    pub event: PaneEventKind,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaneEventResponse {
+    pub account_id: u32,
+    /// Request whose flow owns the pane, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<RequestStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Applies a pane event to the in-progress request leasing that pane, so
+/// abandoned flows do not hang in `InProgress`.
+pub async fn pane_event(
+    State(state): State<AppState>,
+    method: axum::http::Method,
+    uri: axum::http::Uri,
+    headers: HeaderMap,
+    axum::extract::Query(event): axum::extract::Query<PaneEvent>,
+) -> (StatusCode, Json<PaneEventResponse>) {
+    let reject = |code: StatusCode, error: String| {
+        (
+            code,
+            Json(PaneEventResponse {
+                account_id: event.account_id,
+                request_id: None,
+                status: None,
+                error: Some(error),
+            }),
+        )
+    };
+
+    let Some(verifier) = &state.pane_events else {
+        return reject(StatusCode::UNAUTHORIZED, "pane_events_disabled".into());
+    };
+    if let Err(e) = verifier.verify_headers(method.as_str(), &uri, &headers) {
+        warn!(account_id = event.account_id, error = %e, "rejected pane event");
+        return reject(StatusCode::UNAUTHORIZED, format!("invalid_signature: {e}"));
+    }
+
+    let Some(mut req) = state
+        .leases
+        .holder(event.account_id)
+        .and_then(|lease| state.store.get(&lease.request_id))
+        .filter(|req| req.status == RequestStatus::InProgress)
+    else {
+        return (
+            StatusCode::OK,
+            Json(PaneEventResponse {
+                account_id: event.account_id,
+                request_id: None,
+                status: None,
+                error: None,
+            }),
+        );
+    };
+
+    info!(id = %req.id, account_id = event.account_id, event = ?event.event, "pane event");
+    req.updated_at = chrono::Utc::now();
+    match event.event {
+        PaneEventKind::Navigated => {
+            let on_flow = |raw: &str| {
+                let page = url::Url::parse(raw).ok()?;
+                if !matches!(page.scheme(), "http" | "https") {
+                    return None;
+                }
+                state.oauth.get(&req.provider)?.is_flow_page(&page)
+            };
+            // Only a pane that already reached the sign-in can leave it; this
+            // also ignores navigations reported before the flow was loaded.
+            // Providers without login origins are never cancelled this way.
+            let was_on_flow = req.pane_url.as_deref().and_then(on_flow) == Some(true);
+            let left_flow = event.url.as_deref().and_then(on_flow) == Some(false);
+            if was_on_flow && left_flow {
+                req.status = RequestStatus::Cancelled;
+                req.error = Some(format!(
+                    "pane navigated away from the sign-in to {}",
+                    event.url.as_deref().unwrap_or_default()
+                ));
+            }
+            req.pane_url = event.url;
+        }
+        PaneEventKind::LoginEmailDetected => {
+            let expected = state
+                .accounts
+                .get(event.account_id)
+                .and_then(|account| account.expected_email.as_deref());
+            if let (Some(expected), Some(seen)) = (expected, &event.email) {
+                if !seen.eq_ignore_ascii_case(expected) {
+                    // The ID token check stays authoritative; this is an early hint.
+                    warn!(id = %req.id, %expected, %seen, "pane login email differs from expected");
+                }
+            }
+            req.pane_email = event.email;
+        }
+        PaneEventKind::Closed => {
+            req.status = RequestStatus::Cancelled;
+            req.error = Some("pane closed before the flow completed".into());
+        }
+        PaneEventKind::LoadFailed => {
+            req.status = RequestStatus::Error;
+            req.error = Some(format!(
+                "pane failed to load {}: {}",
+                event.url.as_deref().unwrap_or("page"),
+                event.error.as_deref().unwrap_or("unknown error")
+            ));
+        }
+    }
+
+    state.store.update(req.clone());
+    if req.status.is_terminal() {
+        state.leases.release(req.id);
+    }
+
+    (
+        StatusCode::OK,
+        Json(PaneEventResponse {
+            account_id: event.account_id,
+            request_id: Some(req.id),
+            status: Some(req.status),
+            error: None,
+        }),
+    )
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    /// `ok` when the router can drive panes, `degraded` otherwise.
+    pub status: &'static str,
+    pub control_browser: ControlHealth,
+}
+
+/// Readiness: 503 while the control browser is unreachable or its circuit
+/// is open.
+pub async fn get_health(State(state): State<AppState>) -> (StatusCode, Json<HealthResponse>) {
+    let control_browser = state.control.health();
+    // Deployments that only use `print` or `command` drivers do not depend
+    // on Electron being up.
+    let ready = !state.drivers.needs_control_browser() || state.control.is_available();
+    let (code, status) = if ready {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "degraded")
+    };
+    (
+        code,
+        Json(HealthResponse {
+            status,
+            control_browser,
+        }),
+    )
+}
+
 pub async fn oauth_callback(
     State(state): State<AppState>,
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
-) -> (StatusCode, String) {
-    let code = match params.get("code") {
-        Some(v) => v.clone(),
-        None => return (StatusCode::BAD_REQUEST, "missing_code".into()),
+) -> (StatusCode, Html<String>) {
+    let no_return = PageBehavior {
+        cfg: &state.callback_page,
+        return_url: None,
     };
 
     let state_param = match params.get("state") {
         Some(v) => v.clone(),
-        None => return (StatusCode::BAD_REQUEST, "missing_state".into()),
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Html(pages::failure(
+                    "The provider redirected back without identifying the request.",
+                    None,
+                    &no_return,
+                )),
+            )
+        }
     };
 
     let req_id = match parse_uuid(&state_param) {
         Ok(v) => v,
-        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()),
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Html(pages::failure(
+                    "The redirect carried a malformed request identifier.",
+                    None,
+                    &no_return,
+                )),
+            )
+        }
     };
 
-    let mut req = match state.store.get(&req_id) {
-        Some(r) => r,
-        None => return (StatusCode::NOT_FOUND, "request_not_found".into()),
+    let mut req =
+        match state.store.get(&req_id) {
+            Some(r) => r,
+            None => return (
+                StatusCode::NOT_FOUND,
+                Html(pages::failure(
+                    "This sign-in request is unknown to the router; it may have been restarted.",
+                    None,
+                    &no_return,
+                )),
+            ),
+        };
+
+    // A replayed or late redirect must not reopen a finished request.
+    if req.status.is_terminal() {
+        info!(id = %req.id, status = ?req.status, "ignoring redirect for completed request");
+        if req.status == RequestStatus::Approved {
+            return (StatusCode::OK, Html(pages::success(&req, &no_return)));
+        }
+        return (
+            StatusCode::CONFLICT,
+            Html(pages::failure(
+                "This sign-in request has already finished; start a new one to retry.",
+                Some(&req),
+                &no_return,
+            )),
+        );
+    }
+
+    if let Some(provider_error) = ProviderError::from_params(&params) {
+        let err = OAuthError::from(provider_error);
+        info!(id = %req.id, error = %err, "provider returned authorization error");
+        fail_request(&state, &mut req, &err);
+        return (
+            StatusCode::BAD_REQUEST,
+            Html(pages::failure(
+                pages::explain(&err),
+                Some(&req),
+                &page_behavior(&state, &req),
+            )),
+        );
+    }
+
+    let code = match params.get("code") {
+        Some(v) => v.clone(),
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Html(pages::failure(
+                    "The provider redirected back without an authorization code.",
+                    Some(&req),
+                    &page_behavior(&state, &req),
+                )),
+            )
+        }
     };
 
-    match state.oauth.exchange_code(&code).await {
-        Ok(token) => {
-            req.token = Some(token);
-            req.status = RequestStatus::Approved;
-            req.updated_at = chrono::Utc::now();
-            req.error = None;
-            state.store.update(req);
-            (StatusCode::OK, "You may close this window.".into())
+    match redeem_code(&state, &mut req, &code).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Html(pages::success(&req, &page_behavior(&state, &req))),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html(pages::failure(
+                pages::explain(&e),
+                Some(&req),
+                &page_behavior(&state, &req),
+            )),
+        ),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmitCodeRequest {
+    /// Bare authorization code as shown by the provider (`code#state` accepted).
+    #[serde(default)]
+    pub code: Option<String>,
+    /// Full redirect URL copied from the pane's address bar.
+    #[serde(default)]
+    pub redirect_url: Option<String>,
+}
+
+/// Completes a flow whose redirect never reached `/oauth/callback`: the
+/// operator pastes the code (or the whole redirect URL) for the request.
+pub async fn submit_authorization_code(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<SubmitCodeRequest>,
+) -> (StatusCode, Json<StatusResponse>) {
+    let uuid = match parse_uuid(&id) {
+        Ok(v) => v,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(StatusResponse::error(
+                    Uuid::nil(),
+                    format!("invalid_request_id: {e}"),
+                )),
+            )
         }
+    };
+
+    let Some(mut req) = state.store.get(&uuid) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(StatusResponse::error(uuid, "request_not_found".into())),
+        );
+    };
+
+    if req.status.is_terminal() {
+        return (
+            StatusCode::CONFLICT,
+            Json(StatusResponse::from(req).with_error("request_already_completed")),
+        );
+    }
+
+    let params = match manual_code_params(&body) {
+        Ok(params) => params,
         Err(e) => {
-            req.status = RequestStatus::Error;
-            req.updated_at = chrono::Utc::now();
-            req.error = Some(e.to_string());
-            state.store.update(req);
-            (StatusCode::INTERNAL_SERVER_ERROR, "OAuth error".into())
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(StatusResponse::from(req).with_error(e)),
+            )
+        }
+    };
+
+    // Same state validation as the callback: the code must belong to this request.
+    if let Some(submitted_state) = params.get("state") {
+        if parse_uuid(submitted_state).ok() != Some(req.id) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(StatusResponse::from(req).with_error("state_mismatch")),
+            );
+        }
+    } else if body.redirect_url.is_some() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(StatusResponse::from(req).with_error("missing_state")),
+        );
+    }
+
+    if let Some(provider_error) = ProviderError::from_params(&params) {
+        fail_request(&state, &mut req, &OAuthError::from(provider_error));
+        return (StatusCode::OK, Json(StatusResponse::from(req)));
+    }
+
+    let Some(code) = params.get("code") else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(StatusResponse::from(req).with_error("missing_code")),
+        );
+    };
+
+    info!(id = %req.id, "redeeming manually submitted authorization code");
+    let status = match redeem_code(&state, &mut req, code).await {
+        Ok(()) => StatusCode::OK,
+        Err(_) => StatusCode::BAD_GATEWAY,
+    };
+    (status, Json(StatusResponse::from(req)))
+}
+
+/// Normalises a manual submission into redirect-style params
+/// (`code`, `state`, `error`...).
+fn manual_code_params(body: &SubmitCodeRequest) -> Result<HashMap<String, String>, &'static str> {
+    if let Some(raw) = &body.redirect_url {
+        let url = url::Url::parse(raw.trim()).map_err(|_| "invalid_redirect_url")?;
+        // Some providers put the response in the fragment rather than the query.
+        let mut params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        if let Some(fragment) = url.fragment() {
+            params.extend(url::form_urlencoded::parse(fragment.as_bytes()).into_owned());
+        }
+        return Ok(params);
+    }
+
+    let code = body
+        .code
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .ok_or("missing_code")?;
+
+    // Out-of-band pages commonly display `<code>#<state>`.
+    let mut params = HashMap::new();
+    match code.split_once('#') {
+        Some((code, state)) => {
+            params.insert("code".into(), code.to_string());
+            params.insert("state".into(), state.to_string());
+        }
+        None => {
+            params.insert("code".into(), code.to_string());
+        }
+    }
+    Ok(params)
+}
+
+/// Exchanges `code` for the request, verifies identity and scopes, and stores
+/// the outcome. Shared by the redirect callback and manual submission.
+async fn redeem_code(
+    state: &AppState,
+    req: &mut AuthRequest,
+    code: &str,
+) -> Result<(), OAuthError> {
+    let result = exchange_and_verify(state, req, code).await;
+
+    match result {
+        Ok((token, missing)) => {
+            let token = merge_with_base_grant(state, req, token);
+            approve_request(state, req, token, missing);
+            Ok(())
+        }
+        Err(e) => {
+            fail_request(state, req, &e);
+            Err(e)
+        }
+    }
+}
+
+async fn exchange_and_verify(
+    state: &AppState,
+    req: &mut AuthRequest,
+    code: &str,
+) -> Result<(TokenBundle, Vec<String>), OAuthError> {
+    let oauth = state.oauth.client(&req.provider)?;
+    let exchanged = oauth.exchange_code(code, req).await?;
+
+    if let Some(identity) = &exchanged.identity {
+        req.subject = Some(identity.subject.clone());
+        req.email = identity.email.clone();
+    }
+    check_expected_identity(state, req.account_id, exchanged.identity.as_ref())?;
+
+    let missing = oauth.verify_scopes(&req.scopes, &exchanged.token)?;
+    Ok((exchanged.token, missing))
+}
+
+fn incremental(state: &AppState, req: &AuthRequest) -> bool {
+    state
+        .oauth
+        .get(&req.provider)
+        .is_some_and(|oauth| oauth.includes_granted_scopes())
+}
+
+/// For requests extending a grant, keeps the provider's `scope` and, when
+/// the provider includes granted scopes, carries the base grant's refresh
+/// token over if no new one was issued.
+fn merge_with_base_grant(state: &AppState, req: &AuthRequest, token: TokenBundle) -> TokenBundle {
+    let Some(base) = req.base_grant.and_then(|id| state.store.get(&id)) else {
+        return token;
+    };
+    if !incremental(state, req) {
+        // `req.scopes` already held the full union, so the token stands alone.
+        return token;
+    }
+
+    // An omitted `scope` means the request was granted as asked, which with
+    // incremental authorization is the base grant plus the delta.
+    let scope = token.scope.clone().or_else(|| {
+        let mut scopes = base.granted_scopes();
+        for scope in &req.scopes {
+            if !scopes.contains(scope) {
+                scopes.push(scope.clone());
+            }
+        }
+        Some(scopes.join(" "))
+    });
+
+    TokenBundle {
+        refresh_token: token
+            .refresh_token
+            .or_else(|| base.token.and_then(|t| t.refresh_token)),
+        scope,
+        ..token
+    }
+}
+
+fn approve_request(
+    state: &AppState,
+    req: &mut AuthRequest,
+    token: TokenBundle,
+    missing_scopes: Vec<String>,
+) {
+    req.partial = !missing_scopes.is_empty();
+    req.missing_scopes = missing_scopes;
+    let now = chrono::Utc::now();
+    req.token = Some(token);
+    req.issued_at = Some(now);
+    req.status = RequestStatus::Approved;
+    req.updated_at = now;
+    req.error = None;
+    state.store.update(req.clone());
+    state.leases.release(req.id);
+}
+
+/// Moves a request to `Denied` or `Error` depending on the failure.
+fn fail_request(state: &AppState, req: &mut AuthRequest, err: &OAuthError) {
+    req.status = if err.is_denial() {
+        RequestStatus::Denied
+    } else {
+        RequestStatus::Error
+    };
+    req.updated_at = chrono::Utc::now();
+    req.error = Some(err.to_string());
+    state.store.update(req.clone());
+    state.leases.release(req.id);
+}
+
+fn page_behavior<'a>(state: &'a AppState, req: &AuthRequest) -> PageBehavior<'a> {
+    let return_url = if state.callback_page.return_to_startup {
+        req.account_id
+            .and_then(|id| state.accounts.get(id))
+            .and_then(|account| account.startup_url.as_deref())
+    } else {
+        None
+    };
+
+    PageBehavior {
+        cfg: &state.callback_page,
+        return_url,
+    }
+}
+
+/// RFC 8693 §2.1 request; the subject must be an access token this router issued.
+#[derive(Debug, Deserialize)]
+pub struct TokenExchangeRequest {
+    pub grant_type: String,
+    pub subject_token: String,
+    pub subject_token_type: String,
+    #[serde(default)]
+    pub requested_token_type: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub audience: Option<String>,
+    #[serde(default)]
+    pub resource: Option<String>,
+}
+
+/// RFC 8693 §2.2.1 response.
+#[derive(Debug, Serialize)]
+pub struct TokenExchangeResponse {
+    pub access_token: String,
+    pub issued_token_type: String,
+    pub token_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+/// Trades a router-issued access token for a narrower one from the same
+/// provider, recorded as a new approved request linked to the original.
+pub async fn exchange_token(
+    State(state): State<AppState>,
+    Form(body): Form<TokenExchangeRequest>,
+) -> Result<Json<TokenExchangeResponse>, (StatusCode, Json<ProviderError>)> {
+    let reject = |error: &str, description: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ProviderError::new(error, description)),
+        )
+    };
+
+    if body.grant_type != TOKEN_EXCHANGE_GRANT {
+        return Err(reject("unsupported_grant_type", "expected token-exchange"));
+    }
+    if body.subject_token_type != ACCESS_TOKEN_TYPE {
+        return Err(reject("invalid_request", "subject must be an access token"));
+    }
+
+    let parent = match state.store.find_by_token(&body.subject_token) {
+        Some((req, TokenKind::Access)) if req.status == RequestStatus::Approved => req,
+        _ => return Err(reject("invalid_request", "unknown subject token")),
+    };
+    let Some(parent_token) = parent.token.as_ref() else {
+        return Err(reject("invalid_request", "unknown subject token"));
+    };
+    if parent_token
+        .expires_at
+        .is_some_and(|exp| exp <= chrono::Utc::now())
+    {
+        return Err(reject("invalid_request", "subject token expired"));
+    }
+
+    // Exchanged tokens may only narrow what the subject already holds.
+    let held = parent.granted_scopes();
+    let scopes: Vec<String> = match &body.scope {
+        Some(scope) => scope.split_whitespace().map(str::to_string).collect(),
+        None => held.clone(),
+    };
+    if let Some(extra) = scopes.iter().find(|s| !held.contains(s)) {
+        return Err(reject(
+            "invalid_scope",
+            &format!("{extra} exceeds the subject token's scope"),
+        ));
+    }
+
+    let oauth = match state.oauth.client(&parent.provider) {
+        Ok(oauth) if oauth.supports_token_exchange() => oauth,
+        _ => {
+            return Err(reject(
+                "unsupported_grant_type",
+                "provider does not support token exchange",
+            ))
+        }
+    };
+
+    let mut child = AuthRequest::new(
+        parent.client_name.clone(),
+        parent.hostname.clone(),
+        scopes,
+        parent.provider.clone(),
+    );
+    child.account_id = parent.account_id;
+    child.subject = parent.subject.clone();
+    child.email = parent.email.clone();
+    child.exchanged_from = Some(parent.id);
+
+    let params = TokenExchangeParams {
+        subject_token: &body.subject_token,
+        requested_token_type: body.requested_token_type.as_deref(),
+        scopes: &child.scopes,
+        audience: body.audience.as_deref(),
+        resource: body.resource.as_deref(),
+    };
+    let result = oauth
+        .exchange_token(&params, &mut child.exchange_attempts)
+        .await
+        .and_then(|(token, issued)| {
+            let missing = oauth.verify_scopes(&child.scopes, &token)?;
+            Ok((token, issued, missing))
+        });
+
+    match result {
+        Ok((token, issued_token_type, missing)) => {
+            approve_request(&state, &mut child, token.clone(), missing);
+            info!(id = %child.id, parent = %parent.id, "exchanged token");
+            Ok(Json(TokenExchangeResponse {
+                access_token: token.access_token,
+                issued_token_type,
+                token_type: token.token_type,
+                expires_in: token
+                    .expires_at
+                    .map(|exp| (exp - chrono::Utc::now()).num_seconds().max(0)),
+                scope: token.scope,
+                refresh_token: token.refresh_token,
+            }))
+        }
+        Err(e) => {
+            // The child is only stored once approved; the caller never sees
+            // its id, so a failed one would just be an orphan.
+            warn!(parent = %parent.id, error = %e, "token exchange failed");
+            let body = e
+                .provider_error()
+                .cloned()
+                .unwrap_or_else(|| ProviderError::new("server_error", e.to_string()));
+            let status = if e.provider_error().is_some() {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::BAD_GATEWAY
+            };
+            Err((status, Json(body)))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IntrospectionRequest {
+    pub token: String,
+}
+
+/// RFC 7662 response, extended with the router's own request metadata.
+#[derive(Debug, Default, Serialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub async fn introspect_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(body): Form<IntrospectionRequest>,
+) -> (StatusCode, Json<IntrospectionResponse>) {
+    let Some(caller) = authenticate_caller(&state.introspection.callers, &headers) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(IntrospectionResponse {
+                error: Some("invalid_client".into()),
+                ..Default::default()
+            }),
+        );
+    };
+
+    // Unknown tokens are simply inactive; RFC 7662 forbids saying more.
+    let Some((req, kind)) = state.store.find_by_token(&body.token) else {
+        info!(%caller, "introspected unknown token");
+        return (StatusCode::OK, Json(IntrospectionResponse::default()));
+    };
+    let Some(token) = req.token.as_ref() else {
+        return (StatusCode::OK, Json(IntrospectionResponse::default()));
+    };
+
+    let now = chrono::Utc::now();
+    let mut active = req.status == RequestStatus::Approved
+        && match kind {
+            TokenKind::Access => token.expires_at.is_none_or(|exp| exp > now),
+            TokenKind::Refresh => true,
+        };
+
+    if active && state.introspection.proxy_upstream {
+        if let Ok(oauth) = state.oauth.client(&req.provider) {
+            if oauth.endpoints().introspection.is_some() {
+                active = match oauth.introspect(&body.token, kind).await {
+                    Ok(upstream) => upstream,
+                    Err(e) => {
+                        // Fail closed: a token we cannot vouch for is not active.
+                        warn!(id = %req.id, error = %e, "upstream introspection failed");
+                        false
+                    }
+                };
+            }
+        }
+    }
+
+    info!(%caller, id = %req.id, active, "introspected token");
+
+    if !active {
+        return (StatusCode::OK, Json(IntrospectionResponse::default()));
+    }
+
+    (
+        StatusCode::OK,
+        Json(IntrospectionResponse {
+            active,
+            scope: token.scope.clone().or_else(|| Some(req.scopes.join(" "))),
+            token_type: Some(token.token_type.clone()),
+            exp: match kind {
+                TokenKind::Access => token.expires_at.map(|t| t.timestamp()),
+                TokenKind::Refresh => None,
+            },
+            iat: req.issued_at.map(|t| t.timestamp()),
+            sub: req.subject.clone(),
+            username: req.email.clone(),
+            client_name: Some(req.client_name.clone()),
+            hostname: Some(req.hostname.clone()),
+            account_id: req.account_id,
+            error: None,
+        }),
+    )
+}
+
+/// Accepts `Authorization: Basic name:secret` or `Bearer secret` for one of
+/// `callers` (name -> secret) and returns the caller name.
+fn authenticate_caller(callers: &HashMap<String, String>, headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, credentials) = value.split_once(' ')?;
+
+    if scheme.eq_ignore_ascii_case("basic") {
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(credentials.trim())
+            .ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (name, secret) = decoded.split_once(':')?;
+        let expected = callers.get(name)?;
+        return constant_time_eq(expected.as_bytes(), secret.as_bytes()).then(|| name.to_string());
+    }
+
+    if scheme.eq_ignore_ascii_case("bearer") {
+        return callers
+            .iter()
+            .find(|(_, secret)| constant_time_eq(secret.as_bytes(), credentials.trim().as_bytes()))
+            .map(|(name, _)| name.clone());
+    }
+
+    None
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Fails when the pane's configured login email differs from the verified one,
+/// or when there is nothing verified to compare it with.
+fn check_expected_identity(
+    state: &AppState,
+    account_id: Option<u32>,
+    identity: Option<&VerifiedIdentity>,
+) -> Result<(), OAuthError> {
+    let Some(expected) = account_id
+        .and_then(|id| state.accounts.get(id))
+        .and_then(|account| account.expected_email.as_ref())
+    else {
+        return Ok(());
+    };
+
+    let Some(identity) = identity else {
+        warn!(?account_id, %expected, "no id_token to check the login identity against");
+        return Err(OAuthError::IdentityMismatch {
+            expected: expected.clone(),
+            actual: "no id_token".into(),
+        });
+    };
+
+    match &identity.email {
+        Some(actual) if actual.eq_ignore_ascii_case(expected) => Ok(()),
+        actual => {
+            warn!(?account_id, %expected, ?actual, "login identity mismatch");
+            Err(OAuthError::IdentityMismatch {
+                expected: expected.clone(),
+                actual: actual.clone().unwrap_or_else(|| "no verified email".into()),
+            })
         }
     }
 }
@@ -224,3 +1653,385 @@ pub async fn oauth_callback(
 fn parse_uuid(s: &str) -> Result<Uuid, uuid::Error> {
     Uuid::parse_str(s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ControlBrowserConfig, OAuthConfig, SelectionStrategy};
+    use crate::drivers::{BrowserDriver, DriverFuture};
+    use parking_lot::Mutex;
+
+    /// Records the URLs it is asked to open instead of opening them.
+    #[derive(Default)]
+    struct FakeDriver {
+        opened: Mutex<Vec<(u32, String)>>,
+    }
+
+    impl BrowserDriver for FakeDriver {
+        fn open_auth<'a>(&'a self, account_id: u32, auth_url: &'a str) -> DriverFuture<'a> {
+            self.opened.lock().push((account_id, auth_url.to_string()));
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn provider() -> OAuthConfig {
+        OAuthConfig {
+            client_id: "router".into(),
+            client_secret: "secret".into(),
+            issuer: None,
+            auth_url: Some("https://idp.example/authorize".into()),
+            token_url: Some("https://idp.example/token".into()),
+            revocation_url: None,
+            introspection_url: None,
+            userinfo_url: None,
+            jwks_uri: None,
+            redirect_uri: "http://127.0.0.1:7777/oauth/callback".into(),
+            oob_redirect_uri: None,
+            discovery_ttl_secs: 3600,
+            token_auth_method: Default::default(),
+            private_key_path: None,
+            private_key_alg: "RS256".into(),
+            private_key_id: None,
+            connect_timeout_secs: 1,
+            read_timeout_secs: 1,
+            max_retries: 0,
+            retry_backoff_ms: 0,
+            scope_policy: Default::default(),
+            token_exchange: false,
+            incremental_auth_param: None,
+            verification: false,
+            login_origins: Vec::new(),
+        }
+    }
+
+    /// Router state with no control browser running; every account opens
+    /// its URLs through `driver`.
+    fn state_with(driver: Arc<FakeDriver>) -> AppState {
+        let control = ControlClient::new(ControlBrowserConfig {
+            base_url: "http://127.0.0.1:9".into(),
+            health_interval_secs: 10,
+            probe_timeout_ms: 100,
+            signing_secret: None,
+            max_retries: 0,
+            retry_backoff_ms: 0,
+            breaker_threshold: 5,
+            breaker_cooldown_secs: 30,
+        });
+        AppState {
+            store: AuthStore::default(),
+            oauth: OAuthProviders::new(
+                HashMap::from([("default".to_string(), provider())]),
+                "default".into(),
+            ),
+            control,
+            accounts: Arc::new(AccountRegistry::defaults()),
+            leases: AccountLeases::new(600),
+            selector: AccountSelector::new(SelectionStrategy::default()),
+            cooldowns: AccountCooldowns::default(),
+            drivers: BrowserDrivers::from_drivers(HashMap::new(), driver),
+            verification_account: 5,
+            pane_events: None,
+            introspection: Arc::new(IntrospectionConfig {
+                callers: HashMap::new(),
+                proxy_upstream: false,
+            }),
+            callback_page: Arc::new(CallbackPageConfig {
+                auto_close: false,
+                return_to_startup: false,
+                delay_secs: 5,
+            }),
+            service_clients: Arc::new(HashMap::new()),
+        }
+    }
+
+    fn pending_request(state: &AppState) -> AuthRequest {
+        let req = AuthRequest::new(
+            "cli".into(),
+            "host".into(),
+            vec!["basic".into()],
+            "default".into(),
+        );
+        state.store.insert(req.clone());
+        req
+    }
+
+    #[tokio::test]
+    async fn select_account_opens_the_flow_through_the_driver() {
+        let driver = Arc::new(FakeDriver::default());
+        let state = state_with(driver.clone());
+        let req = pending_request(&state);
+
+        let (code, Json(resp)) = select_account(
+            State(state.clone()),
+            Path(req.id.to_string()),
+            Json(SelectAccountRequest {
+                account_id: AccountChoice::Id(2),
+            }),
+        )
+        .await;
+
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(resp.status, RequestStatus::InProgress);
+        let opened = driver.opened.lock().clone();
+        assert_eq!(opened.len(), 1);
+        assert_eq!(opened[0].0, 2);
+        assert!(opened[0].1.starts_with("https://idp.example/authorize?"));
+        assert!(opened[0].1.contains(&format!("state={}", req.id)));
+        assert_eq!(state.leases.holder(2).map(|l| l.request_id), Some(req.id));
+    }
+
+    #[tokio::test]
+    async fn works_without_the_control_browser() {
+        let driver = Arc::new(FakeDriver::default());
+        let state = state_with(driver.clone());
+        let req = pending_request(&state);
+
+        let (code, Json(resp)) = select_account(
+            State(state.clone()),
+            Path(req.id.to_string()),
+            Json(SelectAccountRequest {
+                account_id: AccountChoice::AUTO,
+            }),
+        )
+        .await;
+        assert_eq!(code, StatusCode::OK);
+        assert!(resp.account_id.is_some());
+        assert_eq!(driver.opened.lock().len(), 1);
+
+        let (code, Json(health)) = get_health(State(state)).await;
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(health.status, "ok");
+    }
+
+    #[test]
+    fn auto_assign_keeps_the_verification_pane_free() {
+        // No provider is a verification flow, but requests can still ask
+        // for the pane with `verification: true`.
+        let state = state_with(Arc::new(FakeDriver::default()));
+
+        let mut assigned = Vec::new();
+        loop {
+            let req = pending_request(&state);
+            match auto_assign(&state, &req) {
+                Ok(id) => assigned.push(id),
+                Err(_) => break,
+            }
+        }
+        assert!(!assigned.is_empty());
+        assert!(!assigned.contains(&state.verification_account));
+        assert!(state.leases.holder(state.verification_account).is_none());
+    }
+
+    #[tokio::test]
+    async fn finished_requests_cannot_be_reopened() {
+        let driver = Arc::new(FakeDriver::default());
+        let state = state_with(driver.clone());
+        let mut req = pending_request(&state);
+        req.status = RequestStatus::Approved;
+        state.store.update(req.clone());
+
+        let (code, Json(resp)) = select_account(
+            State(state),
+            Path(req.id.to_string()),
+            Json(SelectAccountRequest {
+                account_id: AccountChoice::Id(1),
+            }),
+        )
+        .await;
+
+        assert_eq!(code, StatusCode::CONFLICT);
+        assert_eq!(resp.error.as_deref(), Some("request_already_completed"));
+        assert!(driver.opened.lock().is_empty());
+    }
+
+    async fn post_pane_event(state: &AppState, query: &str) -> PaneEventResponse {
+        let url = reqwest::Url::parse(&format!("http://router/v1/pane-events?{query}")).unwrap();
+        let signature = crate::signing::RequestSigner::new("secret").sign("POST", &url);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            crate::signing::TIMESTAMP_HEADER,
+            signature.timestamp.to_string().parse().unwrap(),
+        );
+        headers.insert(
+            crate::signing::NONCE_HEADER,
+            signature.nonce.parse().unwrap(),
+        );
+        headers.insert(
+            crate::signing::SIGNATURE_HEADER,
+            signature.signature.parse().unwrap(),
+        );
+        let uri: axum::http::Uri = format!("/v1/pane-events?{query}").parse().unwrap();
+        let event = axum::extract::Query::try_from_uri(&uri).unwrap();
+
+        let (code, Json(resp)) = pane_event(
+            State(state.clone()),
+            axum::http::Method::POST,
+            uri,
+            headers,
+            event,
+        )
+        .await;
+        assert_eq!(code, StatusCode::OK);
+        resp
+    }
+
+    #[tokio::test]
+    async fn leaving_the_sign_in_cancels_the_request() {
+        let driver = Arc::new(FakeDriver::default());
+        let mut state = state_with(driver);
+        state.pane_events = Some(RequestVerifier::new("secret"));
+        state.oauth = OAuthProviders::new(
+            HashMap::from([(
+                "default".to_string(),
+                OAuthConfig {
+                    login_origins: vec!["https://sso.example".into()],
+                    ..provider()
+                },
+            )]),
+            "default".into(),
+        );
+        let req = pending_request(&state);
+        let (code, _) = select_account(
+            State(state.clone()),
+            Path(req.id.to_string()),
+            Json(SelectAccountRequest {
+                account_id: AccountChoice::Id(3),
+            }),
+        )
+        .await;
+        assert_eq!(code, StatusCode::OK);
+
+        let resp = post_pane_event(
+            &state,
+            "account_id=3&event=navigated&url=https%3A%2F%2Fidp.example%2Flogin",
+        )
+        .await;
+        assert_eq!(resp.status, Some(RequestStatus::InProgress));
+
+        let resp = post_pane_event(
+            &state,
+            "account_id=3&event=navigated&url=https%3A%2F%2Fsso.example%2Fsaml",
+        )
+        .await;
+        assert_eq!(resp.status, Some(RequestStatus::InProgress));
+
+        let resp = post_pane_event(
+            &state,
+            "account_id=3&event=navigated&url=https%3A%2F%2Fnews.example%2F",
+        )
+        .await;
+        assert_eq!(resp.request_id, Some(req.id));
+        assert_eq!(resp.status, Some(RequestStatus::Cancelled));
+        assert!(state.leases.holder(3).is_none());
+    }
+
+    #[tokio::test]
+    async fn sso_hops_do_not_cancel_without_login_origins() {
+        let driver = Arc::new(FakeDriver::default());
+        let mut state = state_with(driver);
+        state.pane_events = Some(RequestVerifier::new("secret"));
+        let req = pending_request(&state);
+        let (code, _) = select_account(
+            State(state.clone()),
+            Path(req.id.to_string()),
+            Json(SelectAccountRequest {
+                account_id: AccountChoice::Id(3),
+            }),
+        )
+        .await;
+        assert_eq!(code, StatusCode::OK);
+
+        for url in [
+            "https%3A%2F%2Fidp.example%2Flogin",
+            "https%3A%2F%2Faccounts.google.com%2Fsignin",
+        ] {
+            let resp =
+                post_pane_event(&state, &format!("account_id=3&event=navigated&url={url}")).await;
+            assert_eq!(resp.status, Some(RequestStatus::InProgress));
+        }
+        assert_eq!(
+            state.store.get(&req.id).and_then(|r| r.pane_url).as_deref(),
+            Some("https://accounts.google.com/signin")
+        );
+        assert!(state.leases.holder(3).is_some());
+    }
+
+    fn approved_grant(state: &AppState, scope: &str) -> AuthRequest {
+        let mut base = pending_request(state);
+        base.status = RequestStatus::Approved;
+        base.token = Some(TokenBundle {
+            access_token: "base-token".into(),
+            refresh_token: Some("base-refresh".into()),
+            expires_at: None,
+            token_type: "Bearer".into(),
+            scope: Some(scope.into()),
+        });
+        state.store.update(base.clone());
+        base
+    }
+
+    async fn extend_grant(state: &AppState, base: &AuthRequest) -> AuthRequest {
+        let (code, Json(resp)) = create_token_request(
+            State(state.clone()),
+            HeaderMap::new(),
+            Json(CreateTokenRequest {
+                client_name: base.client_name.clone(),
+                hostname: base.hostname.clone(),
+                scopes: vec!["basic".into(), "code:read".into()],
+                provider: None,
+                out_of_band: false,
+                grant_id: Some(base.id),
+                auto_assign: false,
+                verification: false,
+            }),
+        )
+        .await;
+        assert_eq!(code, StatusCode::ACCEPTED);
+        state.store.get(&resp.request_id).unwrap()
+    }
+
+    #[tokio::test]
+    async fn extending_a_grant_requests_the_union_without_incremental_auth() {
+        let state = state_with(Arc::new(FakeDriver::default()));
+        let base = approved_grant(&state, "basic");
+
+        let req = extend_grant(&state, &base).await;
+        assert_eq!(req.scopes, vec!["basic", "code:read"]);
+
+        // The provider's answer stands; the base refresh token is not reused.
+        let token = TokenBundle {
+            access_token: "new".into(),
+            refresh_token: None,
+            expires_at: None,
+            token_type: "Bearer".into(),
+            scope: Some("basic code:read".into()),
+        };
+        let merged = merge_with_base_grant(&state, &req, token);
+        assert_eq!(merged.scope.as_deref(), Some("basic code:read"));
+        assert_eq!(merged.refresh_token, None);
+    }
+
+    #[tokio::test]
+    async fn extending_a_grant_requests_only_the_delta_with_incremental_auth() {
+        let mut state = state_with(Arc::new(FakeDriver::default()));
+        let mut cfg = provider();
+        cfg.incremental_auth_param = Some("include_granted_scopes".into());
+        state.oauth =
+            OAuthProviders::new(HashMap::from([("default".into(), cfg)]), "default".into());
+        let base = approved_grant(&state, "basic");
+
+        let req = extend_grant(&state, &base).await;
+        assert_eq!(req.scopes, vec!["code:read"]);
+
+        let token = TokenBundle {
+            access_token: "new".into(),
+            refresh_token: None,
+            expires_at: None,
+            token_type: "Bearer".into(),
+            scope: Some("code:read".into()),
+        };
+        let merged = merge_with_base_grant(&state, &req, token);
+        assert_eq!(merged.scope.as_deref(), Some("code:read"));
+        assert_eq!(merged.refresh_token.as_deref(), Some("base-refresh"));
+    }
+}
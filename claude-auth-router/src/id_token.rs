@@ -0,0 +1,126 @@
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::str::FromStr;
+
+use crate::oauth::OAuthError;
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: Option<bool>,
+    #[serde(default)]
+    nonce: Option<String>,
+}
+
+/// Who actually logged in, as asserted by a verified ID token.
+#[derive(Debug, Clone)]
+pub struct VerifiedIdentity {
+    pub subject: String,
+    /// Only populated when the provider did not flag the address as unverified.
+    pub email: Option<String>,
+}
+
+pub async fn fetch_jwks(http: &reqwest::Client, jwks_uri: &str) -> Result<JwkSet, OAuthError> {
+    http.get(jwks_uri)
+        .send()
+        .await
+        .map_err(|e| OAuthError::Http(e.to_string()))?
+        .error_for_status()
+        .map_err(|e| OAuthError::IdToken(format!("jwks fetch: {e}")))?
+        .json()
+        .await
+        .map_err(|e| OAuthError::IdToken(format!("jwks parse: {e}")))
+}
+
+/// Returns the `kid` of the token header, if any, so callers can tell whether
+/// their cached key set needs refreshing before verification.
+pub fn key_id(id_token: &str) -> Result<Option<String>, OAuthError> {
+    decode_header(id_token)
+        .map(|h| h.kid)
+        .map_err(|e| OAuthError::IdToken(format!("header: {e}")))
+}
+
+/// Signing algorithms accepted for a key: its declared `alg`, or else those
+/// its key type supports. Symmetric keys are never accepted.
+fn allowed_algorithms(jwk: &Jwk) -> Result<Vec<Algorithm>, OAuthError> {
+    if let Some(alg) = jwk.common.key_algorithm {
+        return Algorithm::from_str(&alg.to_string())
+            .map(|alg| vec![alg])
+            .map_err(|_| OAuthError::IdToken(format!("key is not a signing key ({alg})")));
+    }
+
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => Ok(vec![
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ]),
+        AlgorithmParameters::EllipticCurve(ec) => match ec.curve {
+            EllipticCurve::P256 => Ok(vec![Algorithm::ES256]),
+            EllipticCurve::P384 => Ok(vec![Algorithm::ES384]),
+            ref other => Err(OAuthError::IdToken(format!("unsupported curve {other:?}"))),
+        },
+        AlgorithmParameters::OctetKeyPair(_) => Ok(vec![Algorithm::EdDSA]),
+        AlgorithmParameters::OctetKey(_) => Err(OAuthError::IdToken(
+            "symmetric keys are not accepted".into(),
+        )),
+    }
+}
+
+/// Verifies signature, `iss`, `aud`, `exp` and `nonce` of an ID token. The
+/// algorithm comes from the matching key, never from the token header.
+pub fn verify(
+    id_token: &str,
+    jwks: &JwkSet,
+    issuer: &str,
+    client_id: &str,
+    expected_nonce: &str,
+) -> Result<VerifiedIdentity, OAuthError> {
+    let header =
+        decode_header(id_token).map_err(|e| OAuthError::IdToken(format!("header: {e}")))?;
+
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or_else(|| OAuthError::IdToken("no matching signing key".into()))?;
+
+    let key = DecodingKey::from_jwk(jwk).map_err(|e| OAuthError::IdToken(format!("key: {e}")))?;
+
+    let allowed = allowed_algorithms(jwk)?;
+    if !allowed.contains(&header.alg) {
+        return Err(OAuthError::IdToken(format!(
+            "algorithm {:?} not allowed for this key",
+            header.alg
+        )));
+    }
+
+    let mut validation = Validation::new(header.alg);
+    validation.algorithms = allowed;
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(|e| OAuthError::IdToken(e.to_string()))?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(expected_nonce) {
+        return Err(OAuthError::IdToken("nonce mismatch".into()));
+    }
+
+    Ok(VerifiedIdentity {
+        subject: claims.sub,
+        email: claims
+            .email
+            .filter(|_| claims.email_verified != Some(false)),
+    })
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::models::RequestId;
+
+/// Exclusive claim on a pane by the request whose flow is running in it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Lease {
+    pub request_id: RequestId,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Lease {
+    fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at > now
+    }
+}
+
+/// One lease per account so two flows never navigate the same pane.
+#[derive(Clone)]
+pub struct AccountLeases {
+    inner: Arc<Mutex<HashMap<u32, Lease>>>,
+    ttl: Duration,
+}
+
+impl AccountLeases {
+    pub fn new(ttl_secs: u64) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(HashMap::new())),
+            ttl: Duration::seconds(ttl_secs as i64),
+        }
+    }
+
+    /// Takes (or renews) the lease on `account_id` for `request_id`, dropping
+    /// any lease the request held on another pane. Returns the current
+    /// holder's lease when the pane is busy.
+    pub fn acquire(&self, account_id: u32, request_id: RequestId) -> Result<Lease, Lease> {
+        let now = Utc::now();
+        let mut leases = self.inner.lock();
+
+        if let Some(held) = leases.get(&account_id) {
+            if held.request_id != request_id && held.is_live(now) {
+                return Err(held.clone());
+            }
+        }
+
+        leases.retain(|id, lease| *id == account_id || lease.request_id != request_id);
+        let lease = Lease {
+            request_id,
+            acquired_at: now,
+            expires_at: now + self.ttl,
+        };
+        leases.insert(account_id, lease.clone());
+        Ok(lease)
+    }
+
+    /// Frees whatever pane `request_id` holds.
+    pub fn release(&self, request_id: RequestId) {
+        self.inner
+            .lock()
+            .retain(|_, lease| lease.request_id != request_id);
+    }
+
+    /// Live lease on `account_id`, if any.
+    pub fn holder(&self, account_id: u32) -> Option<Lease> {
+        let now = Utc::now();
+        self.inner
+            .lock()
+            .get(&account_id)
+            .filter(|lease| lease.is_live(now))
+            .cloned()
+    }
+}
@@ -1,15 +1,30 @@
+mod accounts;
+mod client_auth;
 mod config;
 mod control_client;
+mod cooldowns;
+mod discovery;
+mod drivers;
 mod handlers;
+mod id_token;
+mod leases;
 mod models;
 mod oauth;
+mod pages;
+mod scheduler;
+mod selection;
+mod signing;
 mod store;
 
 use axum::{
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
-use handlers::{create_token_request, get_status, oauth_callback, select_account, AppState};
+use handlers::{
+    clear_cooldown, create_token_request, exchange_token, get_health, get_status, introspect_token,
+    list_accounts, oauth_callback, pane_event, select_account, set_cooldown,
+    submit_authorization_code, AppState,
+};
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
@@ -20,13 +35,75 @@ async fn main() {
 
     let cfg = config::RouterConfig::from_env();
     let store = store::AuthStore::default();
-    let oauth = oauth::OAuthClient::new(cfg.oauth.clone());
+    let oauth = oauth::OAuthProviders::new(cfg.providers.clone(), cfg.default_provider.clone());
+    for (name, client) in oauth.iter() {
+        if let Err(e) = client.discover().await {
+            tracing::warn!(provider = %name, error = %e, "initial oidc discovery failed");
+        }
+        let endpoints = client.endpoints();
+        tracing::info!(
+            provider = %name,
+            authorization = ?endpoints.authorization,
+            token = ?endpoints.token,
+            revocation = ?endpoints.revocation,
+            introspection = ?endpoints.introspection,
+            userinfo = ?endpoints.userinfo,
+            jwks = ?endpoints.jwks,
+            "resolved oauth endpoints"
+        );
+        spawn_discovery_refresh(name.clone(), client.clone());
+    }
+    if oauth.get(oauth.default_name()).is_none() {
+        tracing::warn!(provider = %oauth.default_name(), "default oauth provider is not configured");
+    }
+    let accounts = match &cfg.accounts_file {
+        // Falling back would silently drop the configured emails and
+        // provider restrictions.
+        Some(path) => accounts::AccountRegistry::from_file(path).unwrap_or_else(|e| {
+            tracing::error!(path = %path, error = %e, "failed to load account registry");
+            std::process::exit(1);
+        }),
+        None => accounts::AccountRegistry::defaults(),
+    }
+    .with_overrides(&cfg.expected_emails, &cfg.startup_urls);
+
     let control = control_client::ControlClient::new(cfg.control_browser.clone());
+    let health = control.probe().await;
+    if !health.reachable {
+        tracing::warn!(error = ?health.last_error, "control browser is not reachable");
+    }
+    spawn_control_probe(control.clone());
+
+    let accounts = std::sync::Arc::new(accounts);
+    let cooldowns = cooldowns::AccountCooldowns::load(cfg.timers_file.as_deref());
+    scheduler::TimerScheduler {
+        cooldowns: cooldowns.clone(),
+        control: control.clone(),
+        accounts: accounts.clone(),
+        webhook_url: cfg.availability_webhook.clone(),
+    }
+    .spawn();
+
+    let drivers = drivers::BrowserDrivers::new(&accounts, &cfg.default_driver, &control);
 
     let state = AppState {
         store,
         oauth,
         control,
+        accounts,
+        leases: leases::AccountLeases::new(cfg.account_lease_ttl_secs),
+        selector: selection::AccountSelector::new(cfg.selection_strategy),
+        cooldowns,
+        drivers,
+        verification_account: cfg.verification_account_id,
+        pane_events: cfg
+            .control_browser
+            .signing_secret
+            .as_deref()
+            .map(signing::RequestVerifier::new),
+        introspection: std::sync::Arc::new(cfg.introspection.clone()),
+        callback_page: std::sync::Arc::new(cfg.callback_page.clone()),
+        service_clients: std::sync::Arc::new(cfg.service_clients.clone()),
     };
 
     let app = Router::new()
@@ -36,7 +113,20 @@ async fn main() {
             "/v1/token-requests/:id/select-account",
             post(select_account),
         )
+        .route(
+            "/v1/token-requests/:id/authorization-code",
+            post(submit_authorization_code),
+        )
+        .route("/v1/token-exchange", post(exchange_token))
+        .route("/v1/introspect", post(introspect_token))
         .route("/oauth/callback", get(oauth_callback))
+        .route("/v1/health", get(get_health))
+        .route("/v1/accounts", get(list_accounts))
+        .route("/v1/pane-events", post(pane_event))
+        .route(
+            "/v1/accounts/:id/cooldown",
+            put(set_cooldown).delete(clear_cooldown),
+        )
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind(&cfg.bind_addr)
@@ -45,3 +135,37 @@ async fn main() {
     tracing::info!("Auth router listening on {}", cfg.bind_addr);
     axum::serve(listener, app).await.expect("server error");
 }
+
+/// Keeps discovered provider metadata fresh without waiting for a token exchange.
+fn spawn_discovery_refresh(name: String, oauth: oauth::OAuthClient) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(oauth.discovery_ttl());
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = oauth.refresh_if_stale().await {
+                tracing::warn!(provider = %name, error = %e, "oidc discovery refresh failed");
+            }
+        }
+    });
+}
+
+/// Tracks control-browser reachability so requests can fail fast.
+fn spawn_control_probe(control: control_client::ControlClient) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(control.health_interval());
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let was_reachable = control.is_available();
+            let health = control.probe().await;
+            if health.reachable != was_reachable {
+                tracing::info!(
+                    reachable = health.reachable,
+                    error = ?health.last_error,
+                    "control browser reachability changed"
+                );
+            }
+        }
+    });
+}
@@ -15,6 +15,19 @@ pub enum RequestStatus {
     Error,
 }
 
+impl RequestStatus {
+    /// True once the request can no longer make progress.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            RequestStatus::Approved
+                | RequestStatus::Denied
+                | RequestStatus::Cancelled
+                | RequestStatus::Error
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenBundle {
     pub access_token: String,
@@ -24,34 +37,116 @@ pub struct TokenBundle {
     pub scope: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    Access,
+    Refresh,
+}
+
+impl TokenKind {
+    /// RFC 7662 `token_type_hint` value.
+    pub fn hint(self) -> &'static str {
+        match self {
+            TokenKind::Access => "access_token",
+            TokenKind::Refresh => "refresh_token",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthRequest {
     pub id: RequestId,
     pub client_name: String,
     pub hostname: String,
     pub scopes: Vec<String>,
+    /// Name of the configured OAuth provider this request authenticates against.
+    pub provider: String,
+    /// Use the provider's out-of-band redirect; the code is submitted manually.
+    pub out_of_band: bool,
+    /// Routed to the dedicated verification pane; `account_id` records it.
+    pub verification: bool,
     pub status: RequestStatus,
     pub account_id: Option<u32>,
+    /// OIDC nonce sent with the authorization request and checked in the ID token.
+    pub nonce: String,
+    /// Verified `sub` from the ID token, if the provider returned one.
+    pub subject: Option<String>,
+    /// Verified email from the ID token, if present.
+    pub email: Option<String>,
     pub token: Option<TokenBundle>,
+    /// When `token` was issued; unlike `updated_at` it is not moved by later
+    /// changes to the request.
+    pub issued_at: Option<DateTime<Utc>>,
+    /// Set when the provider granted only some of the requested scopes.
+    pub partial: bool,
+    pub missing_scopes: Vec<String>,
+    /// Approved request this one extends with additional scopes; its grant
+    /// is merged into the resulting token.
+    pub base_grant: Option<RequestId>,
+    /// Request whose token was exchanged (RFC 8693) to obtain this one.
+    pub exchanged_from: Option<RequestId>,
+    /// Token endpoint requests sent for this request's code, retries included.
+    pub exchange_attempts: u32,
+    /// Authorization URL, kept only when the account's driver just records it.
+    pub auth_url: Option<String>,
+    /// Last page the pane reported navigating to.
+    pub pane_url: Option<String>,
+    /// Login email the control browser saw typed into the pane.
+    pub pane_email: Option<String>,
     pub error: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 impl AuthRequest {
-    pub fn new(client_name: String, hostname: String, scopes: Vec<String>) -> Self {
+    pub fn new(
+        client_name: String,
+        hostname: String,
+        scopes: Vec<String>,
+        provider: String,
+    ) -> Self {
         let now = Utc::now();
         Self {
             id: Uuid::new_v4(),
             client_name,
             hostname,
             scopes,
+            provider,
+            out_of_band: false,
+            verification: false,
             status: RequestStatus::Pending,
             account_id: None,
+            nonce: Uuid::new_v4().simple().to_string(),
+            subject: None,
+            email: None,
             token: None,
+            issued_at: None,
+            partial: false,
+            missing_scopes: Vec::new(),
+            base_grant: None,
+            exchanged_from: None,
+            exchange_attempts: 0,
+            auth_url: None,
+            pane_url: None,
+            pane_email: None,
             error: None,
             created_at: now,
             updated_at: now,
         }
     }
+
+    /// Scopes actually held: the token's `scope` when reported, otherwise
+    /// what was requested minus anything known to be missing.
+    pub fn granted_scopes(&self) -> Vec<String> {
+        match self.token.as_ref().and_then(|t| t.scope.as_ref()) {
+            Some(scope) => scope.split_whitespace().map(str::to_string).collect(),
+            None => self
+                .scopes
+                .iter()
+                .filter(|s| !self.missing_scopes.contains(s))
+                .cloned()
+                .collect(),
+        }
+    }
 }
@@ -1,10 +1,19 @@
-use crate::config::OAuthConfig;
-use crate::models::TokenBundle;
+use crate::client_auth;
+use crate::config::{OAuthConfig, ScopePolicy};
+use crate::discovery::{self, CachedMetadata};
+use crate::id_token::{self, VerifiedIdentity};
+use crate::models::{AuthRequest, TokenBundle, TokenKind};
 use chrono::{Duration, Utc};
+use jsonwebtoken::jwk::JwkSet;
+use parking_lot::RwLock;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
 use thiserror::Error;
+use tracing::warn;
 use url::Url;
-use uuid::Uuid;
 
 #[derive(Debug, Error)]
 pub enum OAuthError {
@@ -14,93 +23,676 @@ pub enum OAuthError {
     Http(String),
     #[error("token exchange failed: {0}")]
     Exchange(String),
+    #[error("discovery failed: {0}")]
+    Discovery(String),
+    #[error("missing configuration: {0}")]
+    Config(String),
+    #[error("unknown provider: {0}")]
+    UnknownProvider(String),
+    #[error("id token rejected: {0}")]
+    IdToken(String),
+    #[error("identity mismatch: expected {expected}, got {actual}")]
+    IdentityMismatch { expected: String, actual: String },
+    #[error("insufficient scope: missing {}", .missing.join(" "))]
+    InsufficientScope { missing: Vec<String> },
+    #[error("access denied: {0}")]
+    AccessDenied(ProviderError),
+    #[error("invalid grant: {0}")]
+    InvalidGrant(ProviderError),
+    #[error("invalid client: {0}")]
+    InvalidClient(ProviderError),
+    #[error("invalid scope: {0}")]
+    InvalidScope(ProviderError),
+    #[error("provider unavailable: {0}")]
+    Unavailable(ProviderError),
+    #[error("provider error: {0}")]
+    Provider(ProviderError),
+}
+
+impl OAuthError {
+    /// The provider's RFC 6749 error, when the failure came from one.
+    pub fn provider_error(&self) -> Option<&ProviderError> {
+        match self {
+            OAuthError::AccessDenied(e)
+            | OAuthError::InvalidGrant(e)
+            | OAuthError::InvalidClient(e)
+            | OAuthError::InvalidScope(e)
+            | OAuthError::Unavailable(e)
+            | OAuthError::Provider(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// True when the user (or provider on their behalf) refused the grant,
+    /// as opposed to the flow breaking.
+    pub fn is_denial(&self) -> bool {
+        matches!(self, OAuthError::AccessDenied(_))
+    }
+}
+
+/// RFC 6749 error response, from either the authorization redirect
+/// (§4.1.2.1) or the token endpoint (§5.2).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderError {
+    pub error: String,
+    #[serde(default)]
+    pub error_description: Option<String>,
+    #[serde(default)]
+    pub error_uri: Option<String>,
+}
+
+impl ProviderError {
+    pub fn new(error: &str, description: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            error_description: Some(description.into()),
+            error_uri: None,
+        }
+    }
+
+    /// Reads `error`, `error_description` and `error_uri` from redirect query params.
+    pub fn from_params(params: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            error: params.get("error")?.clone(),
+            error_description: params.get("error_description").cloned(),
+            error_uri: params.get("error_uri").cloned(),
+        })
+    }
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.error)?;
+        if let Some(desc) = &self.error_description {
+            write!(f, " ({desc})")?;
+        }
+        if let Some(uri) = &self.error_uri {
+            write!(f, " see {uri}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ProviderError> for OAuthError {
+    fn from(err: ProviderError) -> Self {
+        match err.error.as_str() {
+            "access_denied" => OAuthError::AccessDenied(err),
+            "invalid_grant" => OAuthError::InvalidGrant(err),
+            "invalid_client" | "unauthorized_client" => OAuthError::InvalidClient(err),
+            "invalid_scope" => OAuthError::InvalidScope(err),
+            "server_error" | "temporarily_unavailable" => OAuthError::Unavailable(err),
+            _ => OAuthError::Provider(err),
+        }
+    }
+}
+
+/// Result of a successful authorization code exchange.
+#[derive(Debug, Clone)]
+pub struct Exchanged {
+    pub token: TokenBundle,
+    /// Present when the provider returned an `id_token` that verified.
+    pub identity: Option<VerifiedIdentity>,
+}
+
+/// Provider endpoints after applying explicit overrides on top of discovery.
+#[derive(Debug, Clone, Default)]
+pub struct Endpoints {
+    pub authorization: Option<String>,
+    pub token: Option<String>,
+    pub revocation: Option<String>,
+    pub introspection: Option<String>,
+    pub userinfo: Option<String>,
+    pub jwks: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct OAuthClient {
     cfg: OAuthConfig,
     http: reqwest::Client,
+    discovered: Arc<RwLock<Option<CachedMetadata>>>,
+    jwks: Arc<RwLock<Option<JwkSet>>>,
 }
 
 impl OAuthClient {
     pub fn new(cfg: OAuthConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .connect_timeout(StdDuration::from_secs(cfg.connect_timeout_secs))
+            .read_timeout(StdDuration::from_secs(cfg.read_timeout_secs))
+            .build()
+            .unwrap_or_else(|e| {
+                warn!(error = %e, "falling back to default http client");
+                reqwest::Client::new()
+            });
+
         Self {
             cfg,
-            http: reqwest::Client::new(),
+            http,
+            discovered: Arc::new(RwLock::new(None)),
+            jwks: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Fetches provider metadata from the configured issuer, replacing the cache.
+    /// A no-op when no issuer is configured.
+    pub async fn discover(&self) -> Result<(), OAuthError> {
+        let Some(issuer) = &self.cfg.issuer else {
+            return Ok(());
+        };
+
+        let metadata = discovery::fetch_metadata(&self.http, issuer).await?;
+        *self.discovered.write() = Some(CachedMetadata {
+            metadata,
+            fetched_at: Utc::now(),
+        });
+        Ok(())
+    }
+
+    /// Re-runs discovery if the cached metadata is missing or older than the TTL.
+    pub async fn refresh_if_stale(&self) -> Result<(), OAuthError> {
+        if self.cfg.issuer.is_none() {
+            return Ok(());
         }
+
+        let ttl = Duration::seconds(self.cfg.discovery_ttl_secs as i64);
+        let stale = match &*self.discovered.read() {
+            Some(cached) => cached.fetched_at + ttl <= Utc::now(),
+            None => true,
+        };
+
+        if stale {
+            self.discover().await?;
+        }
+        Ok(())
+    }
+
+    /// Refresh period for discovered metadata; at least a second, since a
+    /// zero interval would panic the refresh task.
+    pub fn discovery_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.cfg.discovery_ttl_secs.max(1))
+    }
+
+    pub fn endpoints(&self) -> Endpoints {
+        let discovered = self.discovered.read();
+        let meta = discovered.as_ref().map(|c| &c.metadata);
+
+        Endpoints {
+            authorization: self
+                .cfg
+                .auth_url
+                .clone()
+                .or_else(|| meta.map(|m| m.authorization_endpoint.clone())),
+            token: self
+                .cfg
+                .token_url
+                .clone()
+                .or_else(|| meta.map(|m| m.token_endpoint.clone())),
+            revocation: self
+                .cfg
+                .revocation_url
+                .clone()
+                .or_else(|| meta.and_then(|m| m.revocation_endpoint.clone())),
+            introspection: self
+                .cfg
+                .introspection_url
+                .clone()
+                .or_else(|| meta.and_then(|m| m.introspection_endpoint.clone())),
+            userinfo: self
+                .cfg
+                .userinfo_url
+                .clone()
+                .or_else(|| meta.and_then(|m| m.userinfo_endpoint.clone())),
+            jwks: self
+                .cfg
+                .jwks_uri
+                .clone()
+                .or_else(|| meta.and_then(|m| m.jwks_uri.clone())),
+        }
+    }
+
+    /// Issuer expected in ID tokens: the configured one, else the discovered one.
+    fn issuer(&self) -> Option<String> {
+        self.cfg.issuer.clone().or_else(|| {
+            self.discovered
+                .read()
+                .as_ref()
+                .map(|c| c.metadata.issuer.clone())
+        })
+    }
+
+    /// Redirect URI for a request: the provider's out-of-band URI (which shows
+    /// the code for pasting) when asked for, else the router's callback.
+    pub fn redirect_uri(&self, out_of_band: bool) -> Result<&str, OAuthError> {
+        if out_of_band {
+            self.cfg
+                .oob_redirect_uri
+                .as_deref()
+                .ok_or_else(|| OAuthError::Config("out-of-band redirect uri".into()))
+        } else {
+            Ok(&self.cfg.redirect_uri)
+        }
+    }
+
+    /// Whether a page is still part of a sign-in with this provider: it is on
+    /// the issuer's, authorization endpoint's or a redirect URI's origin, or
+    /// on one of the configured `login_origins`. `None` when no login origins
+    /// are configured, since the sites a login passes through (an SSO hop,
+    /// say) are then unknown.
+    pub fn is_flow_page(&self, page: &Url) -> Option<bool> {
+        if self.cfg.login_origins.is_empty() {
+            return None;
+        }
+        let origin = page.origin();
+        let same_origin = |u: &str| Url::parse(u).is_ok_and(|u| u.origin() == origin);
+
+        let endpoints = self.endpoints();
+        [
+            self.issuer(),
+            endpoints.authorization,
+            Some(self.cfg.redirect_uri.clone()),
+            self.cfg.oob_redirect_uri.clone(),
+        ]
+        .iter()
+        .flatten()
+        .chain(&self.cfg.login_origins)
+        .any(|u| same_origin(u))
+        .into()
     }
 
-    pub fn build_auth_url(&self, state: &Uuid, scopes: &[String]) -> Result<String, OAuthError> {
-        let mut url = Url::parse(&self.cfg.auth_url).map_err(|e| OAuthError::Url(e.to_string()))?;
-        let scope = scopes.join(" ");
+    pub fn build_auth_url(&self, req: &AuthRequest) -> Result<String, OAuthError> {
+        let auth_url = self
+            .endpoints()
+            .authorization
+            .ok_or_else(|| OAuthError::Config("authorization endpoint".into()))?;
+        let mut url = Url::parse(&auth_url).map_err(|e| OAuthError::Url(e.to_string()))?;
+        let scope = req.scopes.join(" ");
 
         url.query_pairs_mut()
             .append_pair("response_type", "code")
             .append_pair("client_id", &self.cfg.client_id)
-            .append_pair("redirect_uri", &self.cfg.redirect_uri)
+            .append_pair("redirect_uri", self.redirect_uri(req.out_of_band)?)
             .append_pair("scope", &scope)
-            .append_pair("state", &state.to_string());
+            .append_pair("state", &req.id.to_string())
+            .append_pair("nonce", &req.nonce);
+
+        if let (Some(_), Some(param)) = (req.base_grant, &self.cfg.incremental_auth_param) {
+            url.query_pairs_mut().append_pair(param, "true");
+        }
 
         Ok(url.to_string())
     }
 
-    pub async fn exchange_code(&self, code: &str) -> Result<TokenBundle, OAuthError> {
-        #[derive(Serialize)]
-        struct TokenRequest<'a> {
-            grant_type: &'a str,
-            code: &'a str,
-            client_id: &'a str,
-            client_secret: &'a str,
-            redirect_uri: &'a str,
+    /// Exchanges an authorization code issued for `req`. Every token request
+    /// sent, retries included, is counted in `req.exchange_attempts`.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        req: &mut AuthRequest,
+    ) -> Result<Exchanged, OAuthError> {
+        let token_url = self.token_url().await?;
+
+        let form = vec![
+            ("grant_type", "authorization_code".to_string()),
+            ("code", code.to_string()),
+            (
+                "redirect_uri",
+                self.redirect_uri(req.out_of_band)?.to_string(),
+            ),
+        ];
+        let resp = self
+            .post_token_form(&token_url, form, &mut req.exchange_attempts)
+            .await?;
+
+        let token: TokenResponse = read_token_response(resp).await?;
+
+        let identity = match &token.id_token {
+            Some(raw) if self.can_verify_id_tokens() => {
+                Some(self.verify_id_token(raw, &req.nonce).await?)
+            }
+            // Without an issuer and key set there is nothing to check it
+            // against; accounts expecting an email still fail closed.
+            Some(_) => {
+                warn!("skipping id_token verification: no issuer or jwks uri configured");
+                None
+            }
+            None => None,
+        };
+
+        Ok(Exchanged {
+            token: token.into_bundle(),
+            identity,
+        })
+    }
+
+    /// Obtains a token for the router's own client via the client_credentials
+    /// grant, with no user interaction.
+    pub async fn client_credentials(
+        &self,
+        scopes: &[String],
+        attempts: &mut u32,
+    ) -> Result<TokenBundle, OAuthError> {
+        let token_url = self.token_url().await?;
+
+        let mut form = vec![("grant_type", "client_credentials".to_string())];
+        if !scopes.is_empty() {
+            form.push(("scope", scopes.join(" ")));
         }
+        let resp = self.post_token_form(&token_url, form, attempts).await?;
 
-        #[derive(Deserialize)]
-        struct TokenResponse {
-            access_token: String,
-            #[serde(default)]
-            refresh_token: Option<String>,
-            #[serde(default)]
-            expires_in: Option<i64>,
-            #[serde(default = "default_token_type")]
-            token_type: String,
-            #[serde(default)]
-            scope: Option<String>,
-        }
-
-        let req_body = TokenRequest {
-            grant_type: "authorization_code",
-            code,
-            client_id: &self.cfg.client_id,
-            client_secret: &self.cfg.client_secret,
-            redirect_uri: &self.cfg.redirect_uri,
+        let token: TokenResponse = read_token_response(resp).await?;
+        Ok(token.into_bundle())
+    }
+
+    /// Flows for this provider belong in the verification pane.
+    pub fn is_verification_flow(&self) -> bool {
+        self.cfg.verification
+    }
+
+    /// Incremental authorization is requested with `incremental_auth_param`,
+    /// so new tokens also carry previously granted scopes.
+    pub fn includes_granted_scopes(&self) -> bool {
+        self.cfg.incremental_auth_param.is_some()
+    }
+
+    pub fn supports_token_exchange(&self) -> bool {
+        self.cfg.token_exchange
+    }
+
+    /// RFC 8693 token exchange against the provider's token endpoint. Returns
+    /// the new token and its `issued_token_type`.
+    pub async fn exchange_token(
+        &self,
+        params: &TokenExchangeParams<'_>,
+        attempts: &mut u32,
+    ) -> Result<(TokenBundle, String), OAuthError> {
+        if !self.cfg.token_exchange {
+            return Err(OAuthError::Config("token exchange not enabled".into()));
+        }
+        let token_url = self.token_url().await?;
+
+        let mut form = vec![
+            ("grant_type", TOKEN_EXCHANGE_GRANT.to_string()),
+            ("subject_token", params.subject_token.to_string()),
+            ("subject_token_type", ACCESS_TOKEN_TYPE.to_string()),
+        ];
+        if let Some(v) = params.requested_token_type {
+            form.push(("requested_token_type", v.to_string()));
+        }
+        if !params.scopes.is_empty() {
+            form.push(("scope", params.scopes.join(" ")));
+        }
+        if let Some(v) = params.audience {
+            form.push(("audience", v.to_string()));
+        }
+        if let Some(v) = params.resource {
+            form.push(("resource", v.to_string()));
+        }
+        let resp = self.post_token_form(&token_url, form, attempts).await?;
+
+        let token: TokenResponse = read_token_response(resp).await?;
+        let issued_token_type = token
+            .issued_token_type
+            .clone()
+            .unwrap_or_else(|| ACCESS_TOKEN_TYPE.to_string());
+        Ok((token.into_bundle(), issued_token_type))
+    }
+
+    async fn token_url(&self) -> Result<String, OAuthError> {
+        // A failed refresh keeps serving the previously discovered endpoints.
+        if let Err(e) = self.refresh_if_stale().await {
+            warn!(error = %e, "oidc discovery refresh failed");
+        }
+        self.endpoints()
+            .token
+            .ok_or_else(|| OAuthError::Config("token endpoint".into()))
+    }
+
+    /// Compares granted with requested scopes under the provider's policy and
+    /// returns the requested scopes that were not granted.
+    pub fn verify_scopes(
+        &self,
+        requested: &[String],
+        token: &TokenBundle,
+    ) -> Result<Vec<String>, OAuthError> {
+        // RFC 6749 §5.1: an omitted `scope` means exactly what was requested.
+        let Some(granted) = &token.scope else {
+            return Ok(Vec::new());
         };
+        let granted: Vec<&str> = granted.split_whitespace().collect();
 
-        let resp = self
-            .http
-            .post(&self.cfg.token_url)
-            .form(&req_body)
+        let missing: Vec<String> = requested
+            .iter()
+            .filter(|s| !granted.contains(&s.as_str()))
+            .cloned()
+            .collect();
+
+        if !missing.is_empty() && self.cfg.scope_policy == ScopePolicy::RequireAll {
+            return Err(OAuthError::InsufficientScope { missing });
+        }
+        Ok(missing)
+    }
+
+    /// Posts an authenticated form to a token endpoint, retrying with jittered
+    /// backoff. Connect failures are always retried, since the provider never
+    /// saw the request. Timeouts and 5xx responses are retried only for grants
+    /// that are safe to repeat: an `authorization_code` request may already
+    /// have consumed the code, and replaying it would get `invalid_grant` or
+    /// trip code-reuse revocation. 4xx responses are returned as-is.
+    async fn post_token_form(
+        &self,
+        url: &str,
+        form: Vec<(&'static str, String)>,
+        attempts: &mut u32,
+    ) -> Result<reqwest::Response, OAuthError> {
+        let single_use = form
+            .iter()
+            .any(|(k, v)| *k == "grant_type" && v == "authorization_code");
+        let mut sent = 0;
+        loop {
+            sent += 1;
+            *attempts += 1;
+
+            // Re-authenticate per attempt so client assertions are never reused.
+            let mut body = form.clone();
+            let builder =
+                client_auth::authenticate(&self.cfg, self.http.post(url), &mut body, url)?;
+
+            let can_retry = sent <= self.cfg.max_retries;
+            let retry_reason = match builder.form(&body).send().await {
+                Ok(resp) if can_retry && !single_use && resp.status().is_server_error() => {
+                    resp.status().to_string()
+                }
+                Ok(resp) => return Ok(resp),
+                Err(e) if can_retry && e.is_connect() => e.to_string(),
+                Err(e) if can_retry && !single_use && e.is_timeout() => e.to_string(),
+                Err(e) => return Err(OAuthError::Http(format!("after {sent} attempts: {e}"))),
+            };
+
+            let delay = backoff_delay(self.cfg.retry_backoff_ms, sent);
+            warn!(%url, attempt = sent, ?delay, reason = %retry_reason, "retrying token request");
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Asks the provider's RFC 7662 endpoint whether `token` is still active.
+    pub async fn introspect(&self, token: &str, hint: TokenKind) -> Result<bool, OAuthError> {
+        #[derive(Deserialize)]
+        struct IntrospectionResponse {
+            active: bool,
+        }
+
+        let url = self
+            .endpoints()
+            .introspection
+            .ok_or_else(|| OAuthError::Config("introspection endpoint".into()))?;
+
+        let mut form = vec![
+            ("token", token.to_string()),
+            ("token_type_hint", hint.hint().to_string()),
+        ];
+        let builder = client_auth::authenticate(&self.cfg, self.http.post(&url), &mut form, &url)?;
+
+        let resp: IntrospectionResponse = builder
+            .form(&form)
             .send()
             .await
             .map_err(|e| OAuthError::Http(e.to_string()))?
             .error_for_status()
-            .map_err(|e| OAuthError::Exchange(e.to_string()))?;
-
-        let token: TokenResponse = resp
+            .map_err(|e| OAuthError::Http(e.to_string()))?
             .json()
             .await
-            .map_err(|e| OAuthError::Exchange(e.to_string()))?;
-
-        let expires_at = token
-            .expires_in
-            .map(|secs| Utc::now() + Duration::seconds(secs));
-
-        Ok(TokenBundle {
-            access_token: token.access_token,
-            refresh_token: token.refresh_token,
-            expires_at,
-            token_type: token.token_type,
-            scope: token.scope,
-        })
+            .map_err(|e| OAuthError::Http(e.to_string()))?;
+
+        Ok(resp.active)
+    }
+
+    /// Both an issuer and a JWKS URI are known, explicitly or discovered.
+    fn can_verify_id_tokens(&self) -> bool {
+        self.issuer().is_some() && self.endpoints().jwks.is_some()
+    }
+
+    async fn verify_id_token(
+        &self,
+        raw: &str,
+        nonce: &str,
+    ) -> Result<VerifiedIdentity, OAuthError> {
+        let issuer = self
+            .issuer()
+            .ok_or_else(|| OAuthError::Config("issuer (needed to validate id_token)".into()))?;
+
+        // Refetch the key set when it is missing or lacks the signing key, which
+        // is how providers roll their keys.
+        let kid = id_token::key_id(raw)?;
+        let have_key = match (&*self.jwks.read(), &kid) {
+            (Some(set), Some(kid)) => set.find(kid).is_some(),
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if !have_key {
+            let jwks_uri = self
+                .endpoints()
+                .jwks
+                .ok_or_else(|| OAuthError::Config("jwks uri".into()))?;
+            let set = id_token::fetch_jwks(&self.http, &jwks_uri).await?;
+            *self.jwks.write() = Some(set);
+        }
+
+        let jwks = self
+            .jwks
+            .read()
+            .clone()
+            .ok_or_else(|| OAuthError::IdToken("jwks unavailable".into()))?;
+        id_token::verify(raw, &jwks, &issuer, &self.cfg.client_id, nonce)
+    }
+}
+
+pub const TOKEN_EXCHANGE_GRANT: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// Inputs to an RFC 8693 exchange; the subject is always an access token.
+#[derive(Debug)]
+pub struct TokenExchangeParams<'a> {
+    pub subject_token: &'a str,
+    pub requested_token_type: Option<&'a str>,
+    pub scopes: &'a [String],
+    pub audience: Option<&'a str>,
+    pub resource: Option<&'a str>,
+}
+
+/// RFC 6749 §5.1 successful token response.
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<i64>,
+    #[serde(default = "default_token_type")]
+    token_type: String,
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    id_token: Option<String>,
+    /// RFC 8693 responses only.
+    #[serde(default)]
+    issued_token_type: Option<String>,
+}
+
+impl TokenResponse {
+    fn into_bundle(self) -> TokenBundle {
+        TokenBundle {
+            access_token: self.access_token,
+            refresh_token: self.refresh_token,
+            expires_at: self
+                .expires_in
+                .map(|secs| Utc::now() + Duration::seconds(secs)),
+            token_type: self.token_type,
+            scope: self.scope,
+        }
+    }
+}
+
+/// Exponential backoff with full jitter: uniform in `[0, base * 2^(attempt-1)]`.
+/// Shared by the token endpoint and control-browser retries.
+pub(crate) fn backoff_delay(base_ms: u64, attempt: u32) -> StdDuration {
+    let cap = base_ms.saturating_mul(1u64 << attempt.saturating_sub(1).min(10));
+    StdDuration::from_millis(rand::rng().random_range(0..=cap))
+}
+
+/// Decodes a token endpoint response, turning RFC 6749 §5.2 error bodies
+/// into typed errors.
+async fn read_token_response<T: serde::de::DeserializeOwned>(
+    resp: reqwest::Response,
+) -> Result<T, OAuthError> {
+    let status = resp.status();
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| OAuthError::Http(e.to_string()))?;
+
+    if !status.is_success() {
+        return Err(match serde_json::from_str::<ProviderError>(&body) {
+            Ok(err) => err.into(),
+            Err(_) => OAuthError::Exchange(format!("token endpoint returned {status}")),
+        });
+    }
+
+    serde_json::from_str(&body).map_err(|e| OAuthError::Exchange(e.to_string()))
+}
+
+/// Named OAuth clients; each request is served by the provider it named.
+#[derive(Debug, Clone)]
+pub struct OAuthProviders {
+    clients: Arc<HashMap<String, OAuthClient>>,
+    default: String,
+}
+
+impl OAuthProviders {
+    pub fn new(cfgs: HashMap<String, OAuthConfig>, default: String) -> Self {
+        let clients = cfgs
+            .into_iter()
+            .map(|(name, cfg)| (name, OAuthClient::new(cfg)))
+            .collect();
+        Self {
+            clients: Arc::new(clients),
+            default,
+        }
+    }
+
+    pub fn default_name(&self) -> &str {
+        &self.default
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OAuthClient> {
+        self.clients.get(name)
+    }
+
+    pub fn client(&self, name: &str) -> Result<&OAuthClient, OAuthError> {
+        self.get(name)
+            .ok_or_else(|| OAuthError::UnknownProvider(name.to_string()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &OAuthClient)> {
+        self.clients.iter()
     }
 }
 
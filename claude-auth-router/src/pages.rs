@@ -0,0 +1,181 @@
+use crate::config::CallbackPageConfig;
+use crate::models::AuthRequest;
+use crate::oauth::OAuthError;
+
+/// What the page does once rendered in the pane.
+pub struct PageBehavior<'a> {
+    pub cfg: &'a CallbackPageConfig,
+    /// Pane startup URL to return to, when known and enabled.
+    pub return_url: Option<&'a str>,
+}
+
+pub fn success(req: &AuthRequest, behavior: &PageBehavior<'_>) -> String {
+    let scopes = req
+        .token
+        .as_ref()
+        .and_then(|t| t.scope.clone())
+        .unwrap_or_else(|| req.scopes.join(" "));
+
+    let mut rows = vec![
+        ("Client", req.client_name.clone()),
+        ("Host", req.hostname.clone()),
+        ("Granted scopes", display_or_none(&scopes)),
+    ];
+    if req.partial {
+        rows.push(("Not granted", req.missing_scopes.join(" ")));
+    }
+    if let Some(account_id) = req.account_id {
+        rows.push(("Pane", format!("#{account_id}")));
+    }
+    if let Some(email) = &req.email {
+        rows.push(("Signed in as", email.clone()));
+    }
+
+    render(
+        "Authorization complete",
+        "ok",
+        "The requesting tool now has its token. You may close this window.",
+        &rows,
+        behavior,
+    )
+}
+
+pub fn failure(reason: &str, req: Option<&AuthRequest>, behavior: &PageBehavior<'_>) -> String {
+    let mut rows = Vec::new();
+    if let Some(req) = req {
+        rows.push(("Client", req.client_name.clone()));
+        rows.push(("Host", req.hostname.clone()));
+        if let Some(account_id) = req.account_id {
+            rows.push(("Pane", format!("#{account_id}")));
+        }
+        if let Some(error) = &req.error {
+            rows.push(("Details", error.clone()));
+        }
+    }
+
+    render("Authorization failed", "fail", reason, &rows, behavior)
+}
+
+/// Human explanation of why a flow failed, for the failure page.
+pub fn explain(err: &OAuthError) -> &'static str {
+    match err {
+        OAuthError::AccessDenied(_) => {
+            "Access was declined at the provider, so no token was issued."
+        }
+        OAuthError::InvalidGrant(_) => {
+            "The sign-in code expired or was already used. Start the request again."
+        }
+        OAuthError::InvalidClient(_) | OAuthError::Config(_) | OAuthError::UnknownProvider(_) => {
+            "The router is not configured correctly for this provider. Contact the operator."
+        }
+        OAuthError::InvalidScope(_) => "The provider rejected the permissions that were requested.",
+        OAuthError::Unavailable(_) | OAuthError::Http(_) | OAuthError::Discovery(_) => {
+            "The provider could not be reached or is temporarily unavailable. Try again shortly."
+        }
+        OAuthError::InsufficientScope { .. } => {
+            "The provider granted fewer permissions than the tool needs."
+        }
+        OAuthError::IdToken(_) => "The provider's identity assertion could not be verified.",
+        OAuthError::IdentityMismatch { .. } => {
+            "You signed in with a different account than the one assigned to this pane."
+        }
+        OAuthError::Provider(_) | OAuthError::Exchange(_) | OAuthError::Url(_) => {
+            "The provider returned an unexpected response."
+        }
+    }
+}
+
+fn display_or_none(s: &str) -> String {
+    if s.trim().is_empty() {
+        "(none)".into()
+    } else {
+        s.to_string()
+    }
+}
+
+fn render(
+    title: &str,
+    class: &str,
+    message: &str,
+    rows: &[(&str, String)],
+    behavior: &PageBehavior<'_>,
+) -> String {
+    let rows: String = rows
+        .iter()
+        .map(|(k, v)| format!("<tr><th>{}</th><td>{}</td></tr>", escape(k), escape(v)))
+        .collect();
+
+    let delay_ms = behavior.cfg.delay_secs * 1000;
+    let (notice, script) = match (behavior.return_url, behavior.cfg.auto_close) {
+        (Some(url), _) => (
+            format!(
+                "<p class=\"note\">Returning to the pane's start page in {}s.</p>",
+                behavior.cfg.delay_secs
+            ),
+            format!(
+                "<script>setTimeout(function(){{window.location.replace({});}},{delay_ms});</script>",
+                script_string(url)
+            ),
+        ),
+        (None, true) => (
+            format!(
+                "<p class=\"note\">This window closes in {}s.</p>",
+                behavior.cfg.delay_secs
+            ),
+            format!("<script>setTimeout(function(){{window.close();}},{delay_ms});</script>"),
+        ),
+        (None, false) => (String::new(), String::new()),
+    };
+
+    format!(
+        r#"<!doctype html>
+<html lang="en">
+  <head>
+    <meta charset="UTF-8" />
+    <title>{title}</title>
+    <style>
+      body {{ background: #05060a; color: #e6eef6; font-family: "Inter", "Segoe UI", system-ui, sans-serif; display: flex; justify-content: center; padding-top: 10vh; }}
+      main {{ background: #0a0c12; border: 1px solid #1e2a3a; border-radius: 12px; padding: 24px 32px; max-width: 560px; }}
+      h1.ok {{ color: #4fd18b; }}
+      h1.fail {{ color: #ff6b6b; }}
+      th {{ text-align: left; padding-right: 16px; color: #8aa0b8; font-weight: 600; vertical-align: top; }}
+      td {{ word-break: break-word; }}
+      .note {{ color: #8aa0b8; font-size: 0.9em; }}
+    </style>
+  </head>
+  <body>
+    <main>
+      <h1 class="{class}">{title}</h1>
+      <p>{message}</p>
+      <table>{rows}</table>
+      {notice}
+    </main>
+    {script}
+  </body>
+</html>"#,
+        title = escape(title),
+        message = escape(message),
+    )
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// JSON-quotes a string for inline `<script>`, keeping `</script>` out of it.
+fn script_string(s: &str) -> String {
+    serde_json::to_string(s)
+        .unwrap_or_else(|_| "\"\"".into())
+        .replace("</", "<\\/")
+}
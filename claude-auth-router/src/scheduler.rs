@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::accounts::AccountRegistry;
+use crate::control_client::{ControlClient, ControlClientError, TimerDeadline};
+use crate::cooldowns::{AccountCooldowns, Cooldown};
+
+const TICK: Duration = Duration::from_secs(1);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Body posted to the availability webhook when a cooldown ends.
+#[derive(Debug, Serialize)]
+struct AvailabilityEvent<'a> {
+    event: &'static str,
+    account_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_name: Option<&'a str>,
+    available_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+}
+
+/// Owns the per-account timer schedule: fires deadlines itself and keeps the
+/// control browser's countdown overlays in sync across its restarts.
+pub struct TimerScheduler {
+    pub cooldowns: AccountCooldowns,
+    pub control: ControlClient,
+    pub accounts: Arc<AccountRegistry>,
+    pub webhook_url: Option<String>,
+}
+
+impl TimerScheduler {
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let http = reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .expect("build webhook client");
+            // Deadline last pushed per account since the browser came up.
+            let mut pushed: HashMap<u32, DateTime<Utc>> = HashMap::new();
+            let mut control_up = false;
+            let mut boot_id = None;
+            let mut ticker = tokio::time::interval(TICK);
+            loop {
+                ticker.tick().await;
+
+                for (account_id, cooldown) in self.cooldowns.take_due() {
+                    pushed.remove(&account_id);
+                    self.fire(&http, account_id, cooldown);
+                }
+
+                // Overlays live only in the Electron process; restore them
+                // whenever it (re)appears, including restarts quicker than a
+                // probe interval, which only show up as a new boot id.
+                let available = self.control.is_available();
+                let health = self.control.health();
+                if (available && !control_up) || health.boot_id != boot_id {
+                    pushed.clear();
+                    boot_id = health.boot_id;
+                }
+                control_up = available;
+                if available {
+                    self.push_pending(&mut pushed).await;
+                }
+            }
+        });
+    }
+
+    /// Sends overlays the browser does not have yet, including deadlines that
+    /// have just come within its window.
+    async fn push_pending(&self, pushed: &mut HashMap<u32, DateTime<Utc>>) {
+        for (account_id, cooldown) in self.cooldowns.active() {
+            if pushed.get(&account_id) == Some(&cooldown.available_at) {
+                continue;
+            }
+            let Ok(deadline) = TimerDeadline::new(cooldown.available_at) else {
+                continue;
+            };
+            match self.control.set_timer(account_id, deadline).await {
+                Ok(()) => {
+                    tracing::info!(account_id, "pushed countdown overlay");
+                }
+                // Retried once the browser is reachable again.
+                Err(
+                    ControlClientError::Unavailable(_) | ControlClientError::CircuitOpen { .. },
+                ) => return,
+                Err(e) => {
+                    tracing::warn!(account_id, error = %e, "failed to push countdown overlay");
+                }
+            }
+            pushed.insert(account_id, cooldown.available_at);
+        }
+    }
+
+    /// Logs the deadline and posts the webhook in the background, so a slow
+    /// receiver cannot hold up later timers.
+    fn fire(&self, http: &reqwest::Client, account_id: u32, cooldown: Cooldown) {
+        let account_name = self.accounts.get(account_id).map(|a| a.name.clone());
+        tracing::info!(account_id, ?account_name, "account available again");
+
+        let Some(url) = self.webhook_url.clone() else {
+            return;
+        };
+        let http = http.clone();
+        tokio::spawn(async move {
+            let event = AvailabilityEvent {
+                event: "account_available",
+                account_id,
+                account_name: account_name.as_deref(),
+                available_at: cooldown.available_at,
+                reason: cooldown.reason.as_deref(),
+            };
+            let result = http
+                .post(&url)
+                .json(&event)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status());
+            if let Err(e) = result {
+                tracing::warn!(account_id, error = %e, "availability webhook failed");
+            }
+        });
+    }
+}
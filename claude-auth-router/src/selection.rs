@@ -0,0 +1,144 @@
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::accounts::Account;
+use crate::config::SelectionStrategy;
+
+/// `account_id` in a selection: a concrete pane or `"auto"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AccountChoice {
+    Id(u32),
+    Keyword(AutoKeyword),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoKeyword {
+    Auto,
+}
+
+impl AccountChoice {
+    pub const AUTO: Self = Self::Keyword(AutoKeyword::Auto);
+
+    pub fn id(self) -> Option<u32> {
+        match self {
+            Self::Id(id) => Some(id),
+            Self::Keyword(_) => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SelectorState {
+    last_used: HashMap<u32, DateTime<Utc>>,
+    /// Last account handed out by round-robin.
+    cursor: Option<u32>,
+    /// Pane last used by each `(client_name, hostname)`.
+    sticky: HashMap<(String, String), u32>,
+}
+
+/// Orders eligible panes for automatic assignment.
+#[derive(Clone)]
+pub struct AccountSelector {
+    strategy: SelectionStrategy,
+    state: Arc<Mutex<SelectorState>>,
+}
+
+impl AccountSelector {
+    pub fn new(strategy: SelectionStrategy) -> Self {
+        Self {
+            strategy,
+            state: Arc::new(Mutex::new(SelectorState::default())),
+        }
+    }
+
+    /// Candidate ids, best first. Callers try them in order until a lease
+    /// is acquired, so a lost race falls through to the next pane.
+    /// `registry` gives the round-robin order, which ids need not follow.
+    pub fn rank(
+        &self,
+        registry: &[Account],
+        candidates: &[&Account],
+        client_name: &str,
+        hostname: &str,
+    ) -> Vec<u32> {
+        let state = self.state.lock();
+        let mut lru: Vec<u32> = candidates.iter().map(|a| a.id).collect();
+        // Never-used panes first, then oldest use; ties keep registry order.
+        lru.sort_by_key(|id| state.last_used.get(id).copied());
+
+        match self.strategy {
+            SelectionStrategy::LeastRecentlyUsed => lru,
+            SelectionStrategy::RoundRobin => {
+                // The last pane is usually leased and so not a candidate;
+                // resume after its place in the registry.
+                let index = |id: u32| registry.iter().position(|a| a.id == id);
+                let len = registry.len().max(1);
+                let start = state.cursor.and_then(index).map_or(0, |i| i + 1);
+                let mut ids: Vec<u32> = candidates.iter().map(|a| a.id).collect();
+                ids.sort_by_key(|id| index(*id).map(|i| (i + len - start % len) % len));
+                ids
+            }
+            SelectionStrategy::Sticky => {
+                let key = (client_name.to_string(), hostname.to_string());
+                match state.sticky.get(&key) {
+                    Some(id) if lru.contains(id) => std::iter::once(*id)
+                        .chain(lru.iter().copied().filter(|other| other != id))
+                        .collect(),
+                    _ => lru,
+                }
+            }
+        }
+    }
+
+    /// Notes that a flow was opened in `account_id`.
+    pub fn record(&self, account_id: u32, client_name: &str, hostname: &str) {
+        let mut state = self.state.lock();
+        state.last_used.insert(account_id, Utc::now());
+        state.cursor = Some(account_id);
+        state
+            .sticky
+            .insert((client_name.to_string(), hostname.to_string()), account_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::AccountKind;
+
+    fn account(id: u32) -> Account {
+        Account {
+            id,
+            name: format!("pane {id}"),
+            kind: AccountKind::ClaudeCode,
+            expected_email: None,
+            provider: None,
+            startup_url: None,
+            driver: None,
+        }
+    }
+
+    #[test]
+    fn round_robin_follows_registry_order_not_ids() {
+        let registry = vec![account(8), account(1), account(2)];
+        let selector = AccountSelector::new(SelectionStrategy::RoundRobin);
+
+        let mut picked = Vec::new();
+        for _ in 0..4 {
+            // The pane just picked is leased, so it is not a candidate.
+            let candidates: Vec<&Account> = registry
+                .iter()
+                .filter(|a| picked.last() != Some(&a.id))
+                .collect();
+            let id = selector.rank(&registry, &candidates, "cli", "host")[0];
+            selector.record(id, "cli", "host");
+            picked.push(id);
+        }
+        assert_eq!(picked, vec![8, 1, 2, 8]);
+    }
+}
@@ -0,0 +1,316 @@
+use base64::Engine;
+use chrono::Utc;
+use parking_lot::Mutex;
+use ring::hmac;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+pub const TIMESTAMP_HEADER: &str = "x-control-timestamp";
+pub const NONCE_HEADER: &str = "x-control-nonce";
+pub const SIGNATURE_HEADER: &str = "x-control-signature";
+
+/// How far a signed request's timestamp may drift from the verifier's clock;
+/// nonces are remembered for the same window.
+pub const DEFAULT_MAX_SKEW_SECS: i64 = 60;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SignatureError {
+    #[error("missing {0} header")]
+    Missing(&'static str),
+    #[error("timestamp outside the accepted window")]
+    Stale,
+    #[error("nonce already used")]
+    Replayed,
+    #[error("signature mismatch")]
+    Invalid,
+}
+
+/// Headers carrying a request signature.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub timestamp: i64,
+    pub nonce: String,
+    pub signature: String,
+}
+
+impl Signature {
+    pub fn apply(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        builder
+            .header(TIMESTAMP_HEADER, self.timestamp.to_string())
+            .header(NONCE_HEADER, &self.nonce)
+            .header(SIGNATURE_HEADER, &self.signature)
+    }
+}
+
+/// `METHOD\npath\nquery\ntimestamp\nnonce`, with the query exactly as sent
+/// (no leading `?`).
+fn canonical(method: &str, path: &str, query: &str, timestamp: i64, nonce: &str) -> String {
+    format!(
+        "{}\n{path}\n{query}\n{timestamp}\n{nonce}",
+        method.to_ascii_uppercase()
+    )
+}
+
+/// Signs outgoing requests with HMAC-SHA256 over a shared secret.
+#[derive(Clone)]
+pub struct RequestSigner {
+    key: hmac::Key,
+}
+
+impl RequestSigner {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+        }
+    }
+
+    pub fn sign(&self, method: &str, url: &reqwest::Url) -> Signature {
+        let timestamp = Utc::now().timestamp();
+        let nonce = uuid::Uuid::new_v4().simple().to_string();
+        let message = canonical(
+            method,
+            url.path(),
+            url.query().unwrap_or(""),
+            timestamp,
+            &nonce,
+        );
+        let tag = hmac::sign(&self.key, message.as_bytes());
+
+        Signature {
+            timestamp,
+            nonce,
+            signature: base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(tag.as_ref()),
+        }
+    }
+}
+
+/// Checks signatures produced by [`RequestSigner`] and rejects replays; the
+/// router uses it for pane events posted by the control browser.
+#[derive(Clone)]
+pub struct RequestVerifier {
+    key: hmac::Key,
+    max_skew_secs: i64,
+    /// Nonces seen within the skew window, with their timestamps.
+    seen: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl RequestVerifier {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+            max_skew_secs: DEFAULT_MAX_SKEW_SECS,
+            seen: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Verifies a request given its method, path, raw query and signature
+    /// headers (looked up case-insensitively by the caller).
+    pub fn verify(
+        &self,
+        method: &str,
+        path: &str,
+        query: &str,
+        timestamp: Option<&str>,
+        nonce: Option<&str>,
+        signature: Option<&str>,
+    ) -> Result<(), SignatureError> {
+        let timestamp = timestamp.ok_or(SignatureError::Missing(TIMESTAMP_HEADER))?;
+        let nonce = nonce.ok_or(SignatureError::Missing(NONCE_HEADER))?;
+        let signature = signature.ok_or(SignatureError::Missing(SIGNATURE_HEADER))?;
+
+        let timestamp: i64 = timestamp.parse().map_err(|_| SignatureError::Stale)?;
+        let now = Utc::now().timestamp();
+        if (now - timestamp).abs() > self.max_skew_secs {
+            return Err(SignatureError::Stale);
+        }
+
+        let tag = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| SignatureError::Invalid)?;
+        let message = canonical(method, path, query, timestamp, nonce);
+        hmac::verify(&self.key, message.as_bytes(), &tag).map_err(|_| SignatureError::Invalid)?;
+
+        // Only authentic requests are remembered, so forged ones cannot
+        // burn nonces.
+        let mut seen = self.seen.lock();
+        seen.retain(|_, ts| (now - *ts).abs() <= self.max_skew_secs);
+        if seen.insert(nonce.to_string(), timestamp).is_some() {
+            return Err(SignatureError::Replayed);
+        }
+        Ok(())
+    }
+
+    /// [`verify`](Self::verify) for an incoming axum request.
+    pub fn verify_headers(
+        &self,
+        method: &str,
+        uri: &axum::http::Uri,
+        headers: &axum::http::HeaderMap,
+    ) -> Result<(), SignatureError> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        self.verify(
+            method,
+            uri.path(),
+            uri.query().unwrap_or(""),
+            header(TIMESTAMP_HEADER),
+            header(NONCE_HEADER),
+            header(SIGNATURE_HEADER),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "shared-secret";
+
+    fn sign_at(timestamp: i64, nonce: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, SECRET.as_bytes());
+        let message = canonical("GET", "/open-auth", "account_id=1", timestamp, nonce);
+        let tag = hmac::sign(&key, message.as_bytes());
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(tag.as_ref())
+    }
+
+    fn verify(
+        verifier: &RequestVerifier,
+        timestamp: i64,
+        nonce: &str,
+        signature: &str,
+    ) -> Result<(), SignatureError> {
+        verifier.verify(
+            "GET",
+            "/open-auth",
+            "account_id=1",
+            Some(&timestamp.to_string()),
+            Some(nonce),
+            Some(signature),
+        )
+    }
+
+    #[test]
+    fn matches_the_control_browser_format() {
+        // Computed by the control browser's scheme:
+        // createHmac("sha256", secret).update(`${METHOD}\n${path}\n${query}\n${ts}\n${nonce}`)
+        // .digest("base64url")
+        assert_eq!(
+            canonical(
+                "get",
+                "/open-auth",
+                "account_id=1",
+                1_700_000_000,
+                "0123456789abcdef"
+            ),
+            "GET\n/open-auth\naccount_id=1\n1700000000\n0123456789abcdef"
+        );
+        assert_eq!(
+            sign_at(1_700_000_000, "0123456789abcdef"),
+            "SZIZtvGDcKFHlaD05WsuDANjIVKxqZTYJ7-_-znCTWg"
+        );
+    }
+
+    #[test]
+    fn accepts_what_the_signer_produces() {
+        let url = reqwest::Url::parse("http://127.0.0.1:9222/open-auth?account_id=1").unwrap();
+        let signature = RequestSigner::new(SECRET).sign("GET", &url);
+
+        let result = verify(
+            &RequestVerifier::new(SECRET),
+            signature.timestamp,
+            &signature.nonce,
+            &signature.signature,
+        );
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn rejects_stale_timestamps() {
+        let verifier = RequestVerifier::new(SECRET);
+        let old = Utc::now().timestamp() - DEFAULT_MAX_SKEW_SECS - 5;
+        let ahead = Utc::now().timestamp() + DEFAULT_MAX_SKEW_SECS + 5;
+
+        assert_eq!(
+            verify(&verifier, old, "n1", &sign_at(old, "n1")),
+            Err(SignatureError::Stale)
+        );
+        assert_eq!(
+            verify(&verifier, ahead, "n2", &sign_at(ahead, "n2")),
+            Err(SignatureError::Stale)
+        );
+    }
+
+    #[test]
+    fn rejects_replayed_nonces() {
+        let verifier = RequestVerifier::new(SECRET);
+        let now = Utc::now().timestamp();
+        let signature = sign_at(now, "once");
+
+        assert_eq!(verify(&verifier, now, "once", &signature), Ok(()));
+        assert_eq!(
+            verify(&verifier, now, "once", &signature),
+            Err(SignatureError::Replayed)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        let verifier = RequestVerifier::new(SECRET);
+        let now = Utc::now().timestamp();
+
+        let forged = RequestSigner::new("other-secret").sign(
+            "GET",
+            &reqwest::Url::parse("http://127.0.0.1:9222/open-auth?account_id=1").unwrap(),
+        );
+        assert_eq!(
+            verify(
+                &verifier,
+                forged.timestamp,
+                &forged.nonce,
+                &forged.signature
+            ),
+            Err(SignatureError::Invalid)
+        );
+        assert_eq!(
+            verify(&verifier, now, "n1", "not base64!"),
+            Err(SignatureError::Invalid)
+        );
+
+        // Signed for a different query.
+        let signature = sign_at(now, "n2");
+        let result = verifier.verify(
+            "GET",
+            "/open-auth",
+            "account_id=2",
+            Some(&now.to_string()),
+            Some("n2"),
+            Some(&signature),
+        );
+        assert_eq!(result, Err(SignatureError::Invalid));
+
+        // A rejected forgery does not burn the nonce.
+        assert_eq!(verify(&verifier, now, "n2", &signature), Ok(()));
+    }
+
+    #[test]
+    fn rejects_missing_headers() {
+        let verifier = RequestVerifier::new(SECRET);
+        let now = Utc::now().timestamp().to_string();
+
+        let verify = |timestamp, nonce, signature| {
+            verifier.verify("GET", "/open-auth", "", timestamp, nonce, signature)
+        };
+        assert_eq!(
+            verify(None, Some("n"), Some("s")),
+            Err(SignatureError::Missing(TIMESTAMP_HEADER))
+        );
+        assert_eq!(
+            verify(Some(&now), None, Some("s")),
+            Err(SignatureError::Missing(NONCE_HEADER))
+        );
+        assert_eq!(
+            verify(Some(&now), Some("n"), None),
+            Err(SignatureError::Missing(SIGNATURE_HEADER))
+        );
+    }
+}
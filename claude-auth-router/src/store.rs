@@ -1,4 +1,4 @@
-use crate::models::{AuthRequest, RequestId};
+use crate::models::{AuthRequest, RequestId, TokenKind};
 use parking_lot::RwLock;
 use std::collections::HashMap;
 
@@ -19,4 +19,18 @@ impl AuthStore {
     pub fn get(&self, id: &RequestId) -> Option<AuthRequest> {
         self.inner.read().get(id).cloned()
     }
+
+    /// Finds the request whose issued access or refresh token equals `token`.
+    pub fn find_by_token(&self, token: &str) -> Option<(AuthRequest, TokenKind)> {
+        self.inner.read().values().find_map(|req| {
+            let bundle = req.token.as_ref()?;
+            if bundle.access_token == token {
+                Some((req.clone(), TokenKind::Access))
+            } else if bundle.refresh_token.as_deref() == Some(token) {
+                Some((req.clone(), TokenKind::Refresh))
+            } else {
+                None
+            }
+        })
+    }
 }
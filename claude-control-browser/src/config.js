@@ -22,6 +22,15 @@ const ACCOUNTS = [
 
 const VERIFICATION_VIEW_ID = 5;
 
+// Shared with the router (CONTROL_BROWSER_SECRET there); when set, every
+// command must carry a valid HMAC signature.
+const CONTROL_SECRET = process.env.CONTROL_BROWSER_SECRET || "";
+const CONTROL_MAX_SKEW_SECS = 60;
+
+// Router that receives pane events (navigations, closes, load failures,
+// detected login emails); they are only sent when CONTROL_SECRET is set.
+const ROUTER_URL = process.env.ROUTER_URL || "http://127.0.0.1:7777";
+
 const MIN_WINDOW_WIDTH = 1128;
 const MIN_WINDOW_HEIGHT = 1024;
 const MAX_TIMER_WINDOW_DAYS = 6;
@@ -110,5 +119,8 @@ module.exports = {
   MAIL_POPUP_URL,
   MAIL_POPUP_PARTITION,
   EMAIL_LOGIN_SELECTORS,
-  EMAIL_LOGIN_TEXT_MATCHES
+  EMAIL_LOGIN_TEXT_MATCHES,
+  CONTROL_SECRET,
+  CONTROL_MAX_SKEW_SECS,
+  ROUTER_URL
 };
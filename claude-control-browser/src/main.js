@@ -1,4 +1,5 @@
 const { app, BrowserWindow, session, Menu } = require("electron");
+const crypto = require("crypto");
 const http = require("http");
 const https = require("https");
 const {
@@ -13,7 +14,10 @@ const {
   MAIL_POPUP_PARTITION,
   EMAIL_LOGIN_SELECTORS,
   EMAIL_LOGIN_TEXT_MATCHES,
-  CHROME_USER_AGENT
+  CHROME_USER_AGENT,
+  CONTROL_SECRET,
+  CONTROL_MAX_SKEW_SECS,
+  ROUTER_URL
 } = require("./config");
 const { createAccountViews, layoutViewsInGrid, getViewByAccountId } = require("./layout");
 
@@ -296,6 +300,99 @@ function bindContextMenu(entry) {
   });
 }
 
+// Posts a signed pane event to the router; parameters go in the query so the
+// signature covers them. Best effort: failures are only logged.
+function postPaneEvent(accountId, event, params = {}) {
+  if (!CONTROL_SECRET) return;
+  let url;
+  try {
+    url = new URL("/v1/pane-events", ROUTER_URL);
+  } catch (err) {
+    console.error("Invalid ROUTER_URL", ROUTER_URL);
+    return;
+  }
+  url.searchParams.set("account_id", String(accountId));
+  url.searchParams.set("event", event);
+  Object.entries(params).forEach(([key, value]) => {
+    if (value !== undefined && value !== null && value !== "") {
+      url.searchParams.set(key, String(value));
+    }
+  });
+
+  const timestamp = Math.floor(Date.now() / 1000);
+  const nonce = crypto.randomBytes(16).toString("hex");
+  const query = url.search.replace(/^\?/, "");
+  const message = `POST\n${url.pathname}\n${query}\n${timestamp}\n${nonce}`;
+  const signature = crypto.createHmac("sha256", CONTROL_SECRET).update(message).digest("base64url");
+
+  const client = url.protocol === "https:" ? https : http;
+  const req = client.request(
+    url,
+    {
+      method: "POST",
+      headers: {
+        "x-control-timestamp": String(timestamp),
+        "x-control-nonce": nonce,
+        "x-control-signature": signature,
+        "Content-Length": 0
+      }
+    },
+    (res) => {
+      res.resume();
+      if (res.statusCode >= 400) {
+        console.warn(`Router rejected ${event} for pane ${accountId}: ${res.statusCode}`);
+      }
+    }
+  );
+  req.on("error", (err) => {
+    console.warn(`Failed to post ${event} for pane ${accountId}: ${err.message}`);
+  });
+  req.end();
+}
+
+const LOGIN_EMAIL_PROBE = `(function(){
+  const field = document.querySelector('input[type="email"], input[name*="email" i]');
+  return field && field.value && field.value.includes('@') ? field.value.trim() : null;
+})()`;
+
+function bindPaneEvents(entry) {
+  // Without a secret the router refuses events, so there is nothing to watch.
+  if (!CONTROL_SECRET) return;
+  const { view, account } = entry;
+  const contents = view.webContents;
+  // The prefill is typed in by injectEmailPrefill, not by the user.
+  const prefill = (account.prefillEmail || "").toLowerCase();
+  let lastEmail = null;
+
+  contents.on("did-navigate", (_event, url) => {
+    postPaneEvent(account.id, "navigated", { url });
+  });
+  contents.on("did-fail-load", (_event, errorCode, errorDescription, validatedURL, isMainFrame) => {
+    // -3 is ERR_ABORTED, raised when a navigation is superseded.
+    if (!isMainFrame || errorCode === -3) return;
+    postPaneEvent(account.id, "load_failed", { url: validatedURL, error: errorDescription });
+  });
+  contents.on("render-process-gone", (_event, details) => {
+    postPaneEvent(account.id, "closed", { error: details.reason });
+  });
+  // Report the login email once the user has typed it, before submitting.
+  const emailPoll = setInterval(() => {
+    contents
+      .executeJavaScript(LOGIN_EMAIL_PROBE)
+      .then((email) => {
+        if (email && email !== lastEmail && email.toLowerCase() !== prefill) {
+          lastEmail = email;
+          postPaneEvent(account.id, "login_email_detected", { email });
+        }
+      })
+      .catch(() => {});
+  }, 2000);
+  contents.on("destroyed", () => {
+    clearInterval(emailPoll);
+    postPaneEvent(account.id, "closed");
+  });
+}
+
 function createWindow() {
   mainWindow = new BrowserWindow({
     width: 1500,
@@ -311,6 +408,7 @@ function createWindow() {
     attachContentHelpers(entry.view.webContents, entry.account.name, entry.account.prefillEmail);
     bindWindowOpenHandler(entry.view.webContents);
     bindContextMenu(entry);
+    bindPaneEvents(entry);
   });
   layoutViewsInGrid(mainWindow, views);
 
@@ -773,6 +871,13 @@ function renderControlPage() {
       <h1>Claude Control Browser</h1>
       <p>Eight panes in a 4x2 grid: 7 Claude sessions (mix of Code and Workspace) plus 1 dedicated ChatGPT pane for verification and cross-checks.</p>
       <div class="hint">Right-click inside any pane to open a quick URL prompt or toggle an inline URL bar on that pane.</div>
+${CONTROL_SECRET ? `
+      <div class="card">
+        <h2>Control secret</h2>
+        <label for="control-secret">CONTROL_BROWSER_SECRET</label>
+        <input id="control-secret" type="password" autocomplete="off" />
+        <div class="hint">Commands must be signed. Enter the shared secret to sign the buttons below in this page; it is never sent to the server.</div>
+      </div>` : ""}
       <form id="nav-form" class="card">
         <h2>Navigation</h2>
         <label for="account">Target pane</label>
@@ -821,9 +926,40 @@ function renderControlPage() {
         const timerForm = document.getElementById('timer-form');
         const cancelTimerBtn = document.getElementById('cancel-timer');
 
+        const signed = ${CONTROL_SECRET ? "true" : "false"};
+
+        function base64url(bytes) {
+          let bin = '';
+          new Uint8Array(bytes).forEach((b) => { bin += String.fromCharCode(b); });
+          return btoa(bin).replace(/\\+/g, '-').replace(/\\//g, '_').replace(/=+$/, '');
+        }
+
+        // Same scheme as the router: HMAC-SHA256 over
+        // "METHOD\\npath\\nquery\\ntimestamp\\nnonce", base64url without padding.
+        async function signHeaders(path, query) {
+          const secret = document.getElementById('control-secret').value;
+          if (!secret) throw new Error('Enter the control secret first.');
+          const enc = new TextEncoder();
+          const key = await crypto.subtle.importKey('raw', enc.encode(secret), { name: 'HMAC', hash: 'SHA-256' }, false, ['sign']);
+          const timestamp = String(Math.floor(Date.now() / 1000));
+          const nonce = crypto.randomUUID().replace(/-/g, '');
+          const message = ['GET', path, query, timestamp, nonce].join('\\n');
+          const signature = base64url(await crypto.subtle.sign('HMAC', key, enc.encode(message)));
+          return { 'X-Control-Timestamp': timestamp, 'X-Control-Nonce': nonce, 'X-Control-Signature': signature };
+        }
+
         async function callEndpoint(path, params) {
-          const qs = new URLSearchParams(params);
-          const res = await fetch(path + '?' + qs.toString());
+          const query = new URLSearchParams(params).toString();
+          let headers = {};
+          if (signed) {
+            try {
+              headers = await signHeaders(path, query);
+            } catch (err) {
+              alert(err.message);
+              return null;
+            }
+          }
+          const res = await fetch(path + '?' + query, { headers });
           const data = await res.json();
           alert('Response: ' + JSON.stringify(data));
           return data;
@@ -860,17 +996,66 @@ function renderControlPage() {
   </html>`;
 }
 
+// Nonces of accepted signed commands, kept for the skew window to block replays.
+const seenNonces = new Map();
+
+// Mirrors the router's signer: HMAC-SHA256 over
+// "METHOD\npath\nquery\ntimestamp\nnonce", base64url without padding.
+function verifySignature(req) {
+  const timestamp = req.headers["x-control-timestamp"];
+  const nonce = req.headers["x-control-nonce"];
+  const signature = req.headers["x-control-signature"];
+  if (!timestamp || !nonce || !signature) {
+    return "unsigned";
+  }
+
+  const now = Math.floor(Date.now() / 1000);
+  const ts = parseInt(timestamp, 10);
+  if (isNaN(ts) || Math.abs(now - ts) > CONTROL_MAX_SKEW_SECS) {
+    return "stale";
+  }
+
+  const [path, query = ""] = req.url.split(/\?(.*)/s);
+  const message = `${req.method.toUpperCase()}\n${path}\n${query}\n${ts}\n${nonce}`;
+  const expected = crypto.createHmac("sha256", CONTROL_SECRET).update(message).digest();
+  const provided = Buffer.from(signature, "base64url");
+  if (provided.length !== expected.length || !crypto.timingSafeEqual(provided, expected)) {
+    return "bad_signature";
+  }
+
+  for (const [seen, seenTs] of seenNonces) {
+    if (Math.abs(now - seenTs) > CONTROL_MAX_SKEW_SECS) seenNonces.delete(seen);
+  }
+  if (seenNonces.has(nonce)) {
+    return "replayed";
+  }
+  seenNonces.set(nonce, ts);
+  return null;
+}
+
+// Changes on every start so the router can tell that overlays were lost.
+const BOOT_ID = crypto.randomUUID();
+
 function startControlServer() {
   const controlPage = renderControlPage();
   const server = http.createServer((req, res) => {
     const url = new URL(req.url, "http://127.0.0.1");
 
     if (req.method === "GET" && url.pathname === "/") {
-      res.writeHead(200, { "Content-Type": "text/html" });
+      res.writeHead(200, { "Content-Type": "text/html", "X-Control-Boot-Id": BOOT_ID });
       res.end(controlPage);
       return;
     }
 
+    if (CONTROL_SECRET) {
+      const rejection = verifySignature(req);
+      if (rejection) {
+        res.writeHead(401, { "Content-Type": "application/json" });
+        res.end(JSON.stringify({ status: rejection }));
+        return;
+      }
+    }
+
     if (url.pathname === "/open-auth") {
       const accountId = parseInt(url.searchParams.get("account_id"), 10);
       const authUrl = url.searchParams.get("auth_url");